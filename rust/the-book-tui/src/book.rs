@@ -1,13 +1,15 @@
-use crossterm::{execute, terminal};
-use indicatif::ProgressBar;
 use itertools::Itertools;
-use reqwest::{blocking::Client, header::HeaderMap};
-use serde::Deserialize;
 use std::{
-    fs::{self, File},
-    io::{self, Write},
-    path::PathBuf,
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
 };
+
+use crate::download;
+use crate::include;
+use crate::section;
+use crate::source::BookSource;
+use crate::summary::{self, SummaryItem};
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 static RUST_KEYWORDS: [&str; 52] = [
     "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
@@ -17,103 +19,91 @@ static RUST_KEYWORDS: [&str; 52] = [
     "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
 ];
 
-#[derive(Deserialize, Debug)]
-pub struct GitHubFile {
-    pub path: String,
-}
-
-impl GitHubFile {
-    fn to_rawfile_link(&self) -> String {
-        let mut base = String::from("https://raw.githubusercontent.com/rust-lang/book/main/");
-        base.push_str(self.path.as_str());
-        base
-    }
-}
-
-#[derive(Deserialize)]
-struct GithubResponse {
-    tree: Vec<GitHubFile>,
-}
-
-fn book_dir() -> PathBuf {
-    match home::home_dir() {
-        Some(path) => path.join(".the-book-tui/book"),
-        None => {
-            panic!("Impossible to get your home dir! Please set your $HOME environment variable.")
-        }
-    }
+const SNIPPET_LENGTH: usize = 80;
+/// How much of the line before the match a snippet starts with.
+const SNIPPET_CONTEXT: usize = 20;
+
+/// One paragraph for each section of the file: its heading and
+/// the text up to the next heading.
+pub fn parse_file_into_paragraphs(file_contents: &str) -> Vec<MarkdownParagraph> {
+    section::parse_sections(file_contents)
+        .flatten()
+        .into_iter()
+        .map(|section| MarkdownParagraph {
+            heading: section.heading.clone(),
+            line: section.line,
+            contents: file_contents[section.own_range()].to_string(),
+            relevancy: 0,
+        })
+        // Most chapters start with their heading, leaving nothing before it.
+        .filter(|paragraph| paragraph.heading.is_some() || !paragraph.contents.trim().is_empty())
+        .collect()
 }
 
-pub fn path_to_title(path: &PathBuf) -> String {
-    let raw_title = path
-        .file_stem()
-        .unwrap()
-        .to_os_string()
-        .to_str()
-        .unwrap()
-        .to_string();
-    let pieces = raw_title.split('-').collect::<Vec<&str>>();
-    let capitalized_pieces = pieces
-        .iter()
-        .map(|w| w.to_string().remove(0).to_uppercase().to_string() + &w[1..])
-        .collect::<Vec<String>>();
-    capitalized_pieces.join(" ")
-}
-
-// A paragraph is defined as content after and including a
-// line that stars with a '#' until the next such line.
-pub fn parse_file_into_parapgraphs(file_contents: &String) -> Vec<MarkdownParagraph> {
-    let mut paragraphs: Vec<MarkdownParagraph> = Vec::new();
-
-    let mut paragraph_contents = "".to_string();
-    for line in file_contents.lines() {
-        // Second condition is to exclude derive macro samples
-        // TODO: Make this better
-        if line.starts_with('#') && !line.contains('[') {
-            paragraphs.push(MarkdownParagraph {
-                contents: paragraph_contents,
-                relevancy: 0,
-            });
-            paragraph_contents = "".to_string();
-        } else {
-            paragraph_contents += &(line.to_owned() + "\n");
-        }
-    }
-
-    paragraphs
+/// Include paths are relative to the markdown file that has them.
+fn expand_includes(contents: &str, path: &Path) -> include::Expanded {
+    include::expand_includes(contents, path.parent().unwrap_or(Path::new("")))
 }
 
 #[derive(Clone)]
 pub struct MarkdownFile {
     pub path: PathBuf,
+    /// With the includes expanded.
     pub contents: String,
+    /// The line of the file at `path` each line of `contents` comes from.
+    pub source_lines: Vec<usize>,
     pub title: String,
+    /// Where the chapter is in the table of contents, see `SummaryItem`.
+    pub level: usize,
+    pub number: Option<String>,
     pub relevancy: u64,
     pub paragraphs: Vec<MarkdownParagraph>,
 }
 
 #[derive(Clone)]
 pub struct MarkdownParagraph {
+    /// None for the text before the first heading.
+    pub heading: Option<String>,
+    /// The line of the file the paragraph starts on.
+    pub line: usize,
     pub contents: String,
     pub relevancy: u64,
 }
 
 impl MarkdownFile {
-    pub fn new_from_path(path: PathBuf) -> Self {
-        let title = path_to_title(&path);
-        let contents = fs::read_to_string(path.clone())
-            .unwrap_or_else(|_| panic!("Could not read the file {}", title));
-        let paragraphs = parse_file_into_parapgraphs(&contents);
-
-        Self {
+    /// Reads a chapter listed in `SUMMARY.md` from `src_dir`.
+    pub fn from_summary(src_dir: &Path, item: &SummaryItem, path: &Path) -> Result<Self> {
+        let path = src_dir.join(path);
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        let expanded = expand_includes(&contents, &path);
+        let paragraphs = parse_file_into_paragraphs(&expanded.contents);
+
+        Ok(Self {
             path,
-            contents,
-            title,
+            contents: expanded.contents,
+            source_lines: expanded.source_lines,
+            title: item.title.clone(),
+            level: item.level,
+            number: item.number.clone(),
             relevancy: 0,
             paragraphs,
+        })
+    }
+
+    /// The title as shown in the table of contents.
+    pub fn numbered_title(&self) -> String {
+        match &self.number {
+            Some(number) => format!("{} {}", number, self.title),
+            None => self.title.clone(),
         }
     }
 
+    /// The line of the file at `path` that `line` of `contents` comes from.
+    pub fn source_line(&self, line: usize) -> usize {
+        self.source_lines.get(line).copied().unwrap_or(line)
+    }
+
     pub fn search(mut self, search_phrase: &str) -> Self {
         self.paragraphs = self
             .paragraphs
@@ -121,8 +111,7 @@ impl MarkdownFile {
             .map(|p| p.clone().search(search_phrase))
             .collect();
 
-        self.paragraphs
-            .sort_by(|a, b| b.relevancy.cmp(&a.relevancy));
+        self.paragraphs.sort_by_key(|p| Reverse(p.relevancy));
 
         self.set_relevancy();
         self
@@ -197,69 +186,235 @@ impl MarkdownParagraph {
         self.relevancy = relevancy;
         self
     }
-}
 
-fn get_markdown_pages() -> Result<Vec<MarkdownFile>> {
-    let mut entries = fs::read_dir(book_dir())?
-        .map(|res| res.map(|e| e.path()))
-        .collect::<std::result::Result<Vec<_>, io::Error>>()?;
+    /// The first line that has a word of `needle` in it, to show
+    /// what matched. Long lines are cut around the word.
+    pub fn snippet(&self, needle: &str) -> String {
+        let words = needle.split_whitespace().collect::<Vec<&str>>();
+        let lines = self.contents.lines().map(str::trim);
+
+        let (line, start) = lines
+            .clone()
+            .find_map(|line| {
+                let lowercase = line.to_lowercase();
+                let start = words.iter().find_map(|word| lowercase.find(word))?;
+                Some((line, lowercase[..start].chars().count()))
+            })
+            .or_else(|| Some((lines.clone().find(|line| !line.is_empty())?, 0)))
+            .unwrap_or_default();
+
+        line.chars()
+            .skip(start.saturating_sub(SNIPPET_CONTEXT))
+            .take(SNIPPET_LENGTH)
+            .collect()
+    }
+}
 
-    entries.sort();
+/// A section of a chapter that matches a search.
+pub struct SearchResult {
+    /// Index into the chapters that were searched.
+    pub chapter: usize,
+    pub heading: Option<String>,
+    pub snippet: String,
+    /// The line of the chapter the section starts on.
+    pub line: usize,
+    pub relevancy: u64,
+}
 
-    Ok(entries
+/// The chapters that match `search_phrase`, most relevant first.
+pub fn search_chapters(chapters: &[MarkdownFile], search_phrase: &str) -> Vec<MarkdownFile> {
+    let mut found = chapters
         .iter()
-        .filter(|path| !path.is_dir())
-        .map(|file_path| MarkdownFile::new_from_path(file_path.clone()))
-        .collect::<Vec<MarkdownFile>>())
+        .map(|md_file| md_file.clone().search(search_phrase))
+        .filter(|md_file| md_file.relevancy > 0)
+        .collect::<Vec<MarkdownFile>>();
+
+    found.sort_by_key(|f| Reverse(f.relevancy));
+    found
 }
 
-pub fn read_the_book() -> Result<Vec<MarkdownFile>> {
-    if book_dir().is_dir() {
-        return get_markdown_pages();
+/// The matching sections of chapters returned by `search_chapters`,
+/// most relevant first.
+pub fn search_sections(chapters: &[MarkdownFile], search_phrase: &str) -> Vec<SearchResult> {
+    let mut results = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        for paragraph in chapter.paragraphs.iter().filter(|p| p.relevancy > 0) {
+            results.push(SearchResult {
+                chapter: i,
+                heading: paragraph.heading.clone(),
+                snippet: paragraph.snippet(search_phrase),
+                line: paragraph.line,
+                relevancy: paragraph.relevancy,
+            });
+        }
     }
 
-    println!("Book not found. Downloading...");
+    // Stable, so equally relevant sections stay in chapter order.
+    results.sort_by_key(|result| Reverse(result.relevancy));
+    results
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert("User-Agent", "the-book-tui".parse().unwrap());
+/// A book as loaded from its directory.
+pub struct Book {
+    pub title: String,
+    pub chapters: Vec<MarkdownFile>,
+}
 
-    let client = Client::new();
-    let  git_tree = client.get("https://api.github.com/repos/rust-lang/book/git/trees/21a2ed14f4480dab62438dcc1130291bebc65379?recursive=1")
-        .headers(headers.clone())
-        .send()?
-        .json::<GithubResponse>()?
-        .tree;
+/// Loads the chapters in the order `SUMMARY.md` lists them.
+pub fn get_markdown_pages(dir: &Path) -> Result<Vec<MarkdownFile>> {
+    let summary = dir.join("SUMMARY.md");
+    let contents = fs::read_to_string(&summary)
+        .map_err(|err| format!("Could not read {}: {}", summary.display(), err))?;
 
-    let latest_version: Vec<&GitHubFile> = git_tree
+    summary::parse_summary(&contents)
         .iter()
-        .filter(|f| f.path.starts_with("src/") && f.path.ends_with(".md"))
-        .collect();
+        .filter_map(|item| Some((item, item.path.as_ref()?)))
+        .map(|(item, path)| MarkdownFile::from_summary(dir, item, path))
+        .collect()
+}
+
+/// Loads a book, downloading or cloning it first if it isn't cached yet.
+pub fn read_book(source: &BookSource) -> Result<Book> {
+    if let BookSource::RustBook(edition) = source {
+        let dir = source.cache_dir();
+        if !download::is_intact(&dir) {
+            let downloaded_before = dir.join("src").is_dir();
+            // On stderr, the output of `search` and `show` is for scripts.
+            if downloaded_before {
+                eprintln!("Book is incomplete. Downloading...");
+            } else {
+                eprintln!("Book not found. Downloading...");
+            }
+
+            match download::update_the_book(&dir, edition) {
+                Ok(_) => {}
+                // What is there can still be read.
+                Err(err) if downloaded_before => {
+                    eprintln!("Could not update the book, reading it as it is: {}", err)
+                }
+                Err(err) => {
+                    return Err(format!(
+                        "Could not download the book ({}). Without network access, \
+                         it can be imported from a checkout with `the-book-tui import`",
+                        err
+                    )
+                    .into())
+                }
+            }
+        }
+    }
 
-    let pb = ProgressBar::new(latest_version.iter().len() as u64);
+    let config = source.prepare()?;
+    Ok(Book {
+        title: config.title,
+        chapters: get_markdown_pages(&config.src_dir)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chapter of the Rust book, see `section::tests`.
+    fn example_structs() -> MarkdownFile {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/book/src");
+        let item = SummaryItem {
+            title: String::from("An Example Program Using Structs"),
+            path: Some(PathBuf::from("ch05-02-example-structs.md")),
+            level: 1,
+            number: Some(String::from("5.2.")),
+        };
+        MarkdownFile::from_summary(&src, &item, item.path.as_ref().unwrap()).unwrap()
+    }
+
+    fn chapter(title: &str, contents: &str) -> MarkdownFile {
+        MarkdownFile {
+            path: PathBuf::from(format!("{}.md", title)),
+            contents: contents.to_string(),
+            source_lines: Vec::new(),
+            title: title.to_string(),
+            level: 0,
+            number: None,
+            relevancy: 0,
+            paragraphs: parse_file_into_paragraphs(contents),
+        }
+    }
 
-    for f in latest_version.iter() {
-        let title = f.path.split_once('/').unwrap().1;
-        let rawfile_link = f.to_rawfile_link();
-        let markdown = client
-            .get(rawfile_link)
-            .headers(headers.clone())
-            .send()?
-            .text()?;
+    fn paragraph(contents: &str) -> MarkdownParagraph {
+        MarkdownParagraph {
+            heading: None,
+            line: 0,
+            contents: contents.to_string(),
+            relevancy: 0,
+        }
+    }
 
-        fs::create_dir_all(book_dir())?;
-        let path = book_dir();
-        let path = path.join(title);
+    #[test]
+    fn search_sections_lists_the_best_sections_first() {
+        let chapters = search_chapters(&[example_structs()], "tuple");
+        let results = search_sections(&chapters, "tuple");
+
+        let headings = results
+            .iter()
+            .map(|result| result.heading.as_deref())
+            .collect::<Vec<Option<&str>>>();
+        assert_eq!(
+            headings,
+            [
+                Some("Refactoring with Tuples"),
+                Some("Refactoring with Structs: Adding More Meaning"),
+            ]
+        );
+        assert_eq!(results[0].chapter, 0);
+        assert_eq!(results[0].line, 59);
+        assert!(results[0].relevancy > results[1].relevancy);
+        assert_eq!(results[1].snippet, "e can transform the tuple");
+    }
 
-        let mut file = File::create(path)?;
-        file.write_all(markdown.as_bytes())?;
+    #[test]
+    fn equally_relevant_sections_stay_in_chapter_order() {
+        let chapters = [
+            chapter("Cargo", "# Cargo\n\nCargo builds a crate.\n"),
+            chapter("Rustc", "# Rustc\n\nRustc compiles a crate.\n"),
+        ];
+        let chapters = search_chapters(&chapters, "crate");
+        let results = search_sections(&chapters, "crate");
+
+        let found = results
+            .iter()
+            .map(|result| {
+                (
+                    chapters[result.chapter].title.as_str(),
+                    result.heading.as_deref(),
+                )
+            })
+            .collect::<Vec<(&str, Option<&str>)>>();
+        assert_eq!(found, [("Cargo", Some("Cargo")), ("Rustc", Some("Rustc"))]);
+    }
 
-        pb.inc(1);
+    #[test]
+    fn snippets_show_the_first_line_with_a_searched_word() {
+        let paragraph =
+            paragraph("# Shadowing\n\n  You can declare a new variable.\nVariables shadow.\n");
+        assert_eq!(paragraph.snippet("shadow"), "# Shadowing");
+        assert_eq!(
+            paragraph.snippet("mutable variable"),
+            "u can declare a new variable."
+        );
     }
 
-    pb.finish();
+    #[test]
+    fn snippets_cut_long_lines_around_the_match() {
+        let line = format!("{}needle{}", "ä".repeat(100), "b".repeat(100));
+        let snippet = paragraph(&line).snippet("needle");
 
-    let mut stdout = io::stdout();
-    execute!(stdout, terminal::Clear(terminal::ClearType::All)).unwrap();
+        assert_eq!(snippet.chars().count(), SNIPPET_LENGTH);
+        assert_eq!(snippet.find("needle"), Some("ä".len() * SNIPPET_CONTEXT));
+    }
 
-    return get_markdown_pages();
+    #[test]
+    fn snippets_fall_back_to_the_first_line_with_text() {
+        assert_eq!(paragraph("\n\nFirst\nSecond").snippet("python"), "First");
+        assert_eq!(paragraph("").snippet("python"), "");
+    }
 }
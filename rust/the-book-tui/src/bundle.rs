@@ -0,0 +1,225 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use crate::book::Result;
+use crate::download::{self, sibling};
+use crate::include;
+use crate::source::BookSource;
+
+/// Packs the directory the Rust Book is kept in into a gzipped
+/// tarball, which `import` can read on another machine.
+pub fn export(source: &BookSource, tarball: &Path) -> Result<()> {
+    if !matches!(source, BookSource::RustBook(_)) {
+        return Err("Only The Rust Book is exported, other books are opened with --book".into());
+    }
+
+    let exported = export_dir(&source.cache_dir(), tarball)?;
+    println!("Exported {} files to {}", exported, tarball.display());
+    Ok(())
+}
+
+/// Returns how many files were packed.
+fn export_dir(dir: &Path, tarball: &Path) -> Result<usize> {
+    if !dir.is_dir() {
+        return Err(format!("There is no book in {} to export", dir.display()).into());
+    }
+
+    let mut files = Vec::new();
+    find_files(dir, dir, &mut files)?;
+
+    let encoder = GzEncoder::new(File::create(tarball)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for path in files.iter() {
+        builder.append_path_with_name(dir.join(path), path)?;
+    }
+    builder.into_inner()?.finish()?;
+
+    Ok(files.len())
+}
+
+/// Replaces the cached Rust Book with one from a tarball made by
+/// `export` or a checkout of the book's repository, a directory or
+/// a tarball of one. Nothing is downloaded.
+pub fn import(source: &BookSource, from: &Path) -> Result<()> {
+    if !matches!(source, BookSource::RustBook(_)) {
+        return Err("Only The Rust Book is imported, other books are opened with --book".into());
+    }
+
+    let book_dir = source.cache_dir();
+    let imported = import_into(&book_dir, from)?;
+    println!("Imported {} files into {}", imported, book_dir.display());
+    Ok(())
+}
+
+/// Returns how many files were imported.
+fn import_into(book_dir: &Path, from: &Path) -> Result<usize> {
+    // Built next to the book so a failed import leaves it as it was.
+    let staging = sibling(book_dir, "import");
+    let _ = fs::remove_dir_all(&staging);
+
+    let imported = if from.is_dir() {
+        copy_book(from, &staging)
+    } else {
+        unpack_book(from, &staging)
+    };
+    let imported = match imported {
+        Ok(imported) => imported,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+    };
+
+    if let Some(parent) = book_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    download::replace_dir(book_dir, &staging)?;
+    Ok(imported)
+}
+
+fn unpack_book(tarball: &Path, to: &Path) -> Result<usize> {
+    let unpacked = sibling(to, "unpacked");
+    let _ = fs::remove_dir_all(&unpacked);
+
+    let file = File::open(tarball)
+        .map_err(|err| format!("Could not open {}: {}", tarball.display(), err))?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(&unpacked)
+        .map_err(|err| format!("Could not unpack {}: {}", tarball.display(), err))?;
+
+    let copied = copy_book(&book_root(&unpacked)?, to);
+    let _ = fs::remove_dir_all(&unpacked);
+    copied
+}
+
+/// Tarballs of a repository, like GitHub's, have everything in one directory.
+fn book_root(unpacked: &Path) -> Result<PathBuf> {
+    if unpacked.join("src").is_dir() {
+        return Ok(unpacked.to_path_buf());
+    }
+
+    let entries = fs::read_dir(unpacked)?.collect::<std::io::Result<Vec<_>>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().is_dir() => Ok(entry.path()),
+        _ => Ok(unpacked.to_path_buf()),
+    }
+}
+
+/// Copies the chapters in `checkout` and the listings they include,
+/// the same files a download gets, and returns how many there are.
+fn copy_book(checkout: &Path, to: &Path) -> Result<usize> {
+    let src = checkout.join("src");
+    if !src.join("SUMMARY.md").is_file() {
+        return Err(format!(
+            "{} is not the book, it has no src/SUMMARY.md",
+            checkout.display()
+        )
+        .into());
+    }
+
+    let mut chapters = Vec::new();
+    find_files(checkout, &src, &mut chapters)?;
+    chapters.retain(|path| path.extension().is_some_and(|ext| ext == "md"));
+
+    let mut paths = chapters.clone();
+    for chapter in chapters.iter() {
+        let markdown = fs::read_to_string(checkout.join(chapter))?;
+        let dir = chapter.parent().unwrap_or(Path::new(""));
+        for path in include::included_paths(&markdown, dir) {
+            if checkout.join(&path).is_file() && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    for path in paths.iter() {
+        let target = to.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(checkout.join(path), target)?;
+    }
+
+    let paths = paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+    download::record_files(to, &paths)?;
+
+    Ok(paths.len())
+}
+
+/// Collects the files in `dir` and its subdirectories relative
+/// to `base`. Git's own files are left out.
+fn find_files(base: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+        if path.is_dir() {
+            find_files(base, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests run in parallel, so each gets its own `dir`.
+    fn temp_dir(dir: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("the-book-tui-bundle").join(dir);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = Vec::new();
+        find_files(dir, dir, &mut files).unwrap();
+        files.sort();
+        files
+            .into_iter()
+            .map(|path| {
+                let contents = fs::read(dir.join(&path)).unwrap();
+                (path, contents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_books_import_as_they_were() {
+        let dir = temp_dir("round-trip");
+        let checkout = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/book");
+        let (book_dir, tarball, imported) = (
+            dir.join("book"),
+            dir.join("book.tar.gz"),
+            dir.join("imported"),
+        );
+
+        let count = import_into(&book_dir, &checkout).unwrap();
+        assert!(download::is_intact(&book_dir));
+        // The summary, the chapter and the three listings it includes.
+        assert_eq!(count, 5);
+
+        export_dir(&book_dir, &tarball).unwrap();
+        assert_eq!(import_into(&imported, &tarball).unwrap(), count);
+        assert!(download::is_intact(&imported));
+        assert_eq!(files(&imported), files(&book_dir));
+    }
+
+    #[test]
+    fn only_the_rust_book_is_exported() {
+        let dir = temp_dir("local");
+        let source = BookSource::Local(dir.clone());
+        assert!(export(&source, &dir.join("book.tar.gz")).is_err());
+        assert!(!dir.join("book.tar.gz").exists());
+    }
+}
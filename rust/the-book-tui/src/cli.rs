@@ -0,0 +1,199 @@
+use serde::Serialize;
+use std::{io::Write, path::PathBuf};
+
+use crate::book::{self, Book, MarkdownFile, Result};
+
+/// A search result as printed with `--json`.
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    chapter: &'a str,
+    number: Option<&'a str>,
+    heading: Option<&'a str>,
+    path: &'a PathBuf,
+    /// The line of the file at `path`, counting from 1 like editors do.
+    line: usize,
+    snippet: &'a str,
+    relevancy: u64,
+}
+
+/// Prints the `limit` sections that match `query` best.
+pub fn search(
+    book: &Book,
+    query: &str,
+    limit: usize,
+    json: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    let query = query.trim().to_lowercase();
+    let chapters = book::search_chapters(&book.chapters, &query);
+    let results = book::search_sections(&chapters, &query);
+
+    let results = results
+        .iter()
+        .take(limit)
+        .map(|result| {
+            let chapter = &chapters[result.chapter];
+            JsonResult {
+                chapter: &chapter.title,
+                number: chapter.number.as_deref(),
+                heading: result.heading.as_deref(),
+                path: &chapter.path,
+                line: chapter.source_line(result.line) + 1,
+                snippet: &result.snippet,
+                relevancy: result.relevancy,
+            }
+        })
+        .collect::<Vec<JsonResult>>();
+
+    if json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&results)?)?;
+        return Ok(());
+    }
+
+    if results.is_empty() {
+        writeln!(out, "No results for \"{}\"", query)?;
+    }
+    for result in results {
+        let title = match (result.number, result.heading) {
+            (Some(number), Some(heading)) => format!("{} {} › {}", number, result.chapter, heading),
+            (None, Some(heading)) => format!("{} › {}", result.chapter, heading),
+            (Some(number), None) => format!("{} {}", number, result.chapter),
+            (None, None) => result.chapter.to_string(),
+        };
+        writeln!(out, "{}", title)?;
+        writeln!(out, "    {}:{}", result.path.display(), result.line)?;
+        writeln!(out, "    {}", result.snippet)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the markdown of a chapter, found by its number
+/// like `4.1`, its title or the name of its file.
+pub fn show(book: &Book, chapter: &str, out: &mut impl Write) -> Result<()> {
+    let found = book
+        .chapters
+        .iter()
+        .find(|md_file| is_chapter(md_file, chapter))
+        .ok_or_else(|| format!("No chapter {} in {}", chapter, book.title))?;
+
+    write!(out, "{}", found.contents)?;
+    Ok(())
+}
+
+fn is_chapter(md_file: &MarkdownFile, chapter: &str) -> bool {
+    let number = md_file
+        .number
+        .as_deref()
+        .map(|number| number.trim_end_matches('.'));
+    let stem = md_file.path.file_stem().and_then(|stem| stem.to_str());
+
+    number == Some(chapter.trim_end_matches('.'))
+        || md_file.title.eq_ignore_ascii_case(chapter)
+        || stem == Some(chapter.trim_end_matches(".md"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SUMMARY: &str = "\
+# Summary
+
+[Foreword](foreword.md)
+
+- [Getting Started](ch01-00-getting-started.md)
+";
+
+    const CHAPTER: &str = "\
+# Getting Started
+
+```rust
+{{#include listing.rs}}
+```
+
+## Installation
+
+Install rustup first.
+";
+
+    const LISTING: &str = "\
+fn main() {
+    println!(\"Hello\");
+}
+";
+
+    /// Tests run in parallel, so each gets its own `dir`.
+    fn book(dir: &str) -> Book {
+        let dir = std::env::temp_dir().join("the-book-tui-cli").join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("SUMMARY.md"), SUMMARY).unwrap();
+        fs::write(dir.join("foreword.md"), "# Foreword\n\nWelcome.\n").unwrap();
+        fs::write(dir.join("ch01-00-getting-started.md"), CHAPTER).unwrap();
+        fs::write(dir.join("listing.rs"), LISTING).unwrap();
+
+        Book {
+            title: String::from("Test Book"),
+            chapters: book::get_markdown_pages(&dir).unwrap(),
+        }
+    }
+
+    fn output(run: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+        let mut out = Vec::new();
+        run(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn search_prints_the_line_of_the_section_in_its_file() {
+        let book = book("search");
+        let path = book.chapters[1].path.display();
+
+        assert_eq!(
+            output(|out| search(&book, "rustup", 5, false, out)),
+            format!(
+                "1. Getting Started › Installation\n    {}:7\n    Install rustup first.\n\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn search_prints_json() {
+        let book = book("json");
+        let printed = output(|out| search(&book, "Rustup", 5, true, out));
+        let results: serde_json::Value = serde_json::from_str(&printed).unwrap();
+
+        assert_eq!(results[0]["chapter"], "Getting Started");
+        assert_eq!(results[0]["number"], "1.");
+        assert_eq!(results[0]["heading"], "Installation");
+        assert_eq!(results[0]["line"], 7);
+        assert_eq!(results[0]["snippet"], "Install rustup first.");
+        assert_eq!(results.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn search_says_when_nothing_matches() {
+        let book = book("nothing");
+        assert_eq!(
+            output(|out| search(&book, "python", 5, false, out)),
+            "No results for \"python\"\n"
+        );
+    }
+
+    #[test]
+    fn show_finds_chapters_by_number_title_or_file() {
+        let book = book("show");
+        let expanded = CHAPTER.replace("{{#include listing.rs}}\n", LISTING);
+
+        assert_eq!(output(|out| show(&book, "1", out)), expanded);
+        assert_eq!(output(|out| show(&book, "getting started", out)), expanded);
+        assert_eq!(
+            output(|out| show(&book, "foreword.md", out)),
+            "# Foreword\n\nWelcome.\n"
+        );
+        assert!(show(&book, "2", &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,664 @@
+use indicatif::ProgressBar;
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::book::Result;
+use crate::include;
+
+const REPOSITORY: &str = "rust-lang/book";
+/// Kept in the book's directory to know what was downloaded.
+const MANIFEST: &str = ".manifest.json";
+/// A GitHub token raises the rate limit of the API from 60 requests an hour.
+pub const TOKEN_ENV_VAR: &str = "THE_BOOK_TUI_GITHUB_TOKEN";
+/// How many files are downloaded at the same time.
+const WORKERS: usize = 8;
+const MAX_ATTEMPTS: u32 = 4;
+/// The first retry waits this long, every next one twice as long.
+const BACKOFF: Duration = Duration::from_millis(500);
+/// Longer waits for the rate limit to reset give up instead.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Debug)]
+pub struct GitHubFile {
+    pub path: String,
+    /// The blob SHA, it changes whenever the file does.
+    pub sha: String,
+}
+
+#[derive(Deserialize)]
+struct GithubResponse {
+    tree: Vec<GitHubFile>,
+}
+
+#[derive(Deserialize)]
+struct GitHubCommit {
+    sha: String,
+    commit: CommitDetails,
+}
+
+#[derive(Deserialize)]
+struct CommitDetails {
+    tree: TreeRef,
+}
+
+#[derive(Deserialize)]
+struct TreeRef {
+    sha: String,
+}
+
+/// What is on disk: the commit it was downloaded
+/// from and the blob SHA of every file, by path.
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    commit: String,
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    fn read(book_dir: &Path) -> Self {
+        fs::read_to_string(book_dir.join(MANIFEST))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, book_dir: &Path) -> Result<()> {
+        fs::create_dir_all(book_dir)?;
+        fs::write(book_dir.join(MANIFEST), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Whether every file is on disk with the contents it was written with.
+    fn verify(&self, book_dir: &Path) -> bool {
+        !self.files.is_empty()
+            && self
+                .files
+                .iter()
+                .all(|(path, sha)| is_on_disk(book_dir, path, sha))
+    }
+}
+
+/// Git's SHA of a file, the one the tree response has.
+fn blob_sha(contents: &[u8]) -> String {
+    let mut sha = sha1_smol::Sha1::new();
+    sha.update(format!("blob {}\0", contents.len()).as_bytes());
+    sha.update(contents);
+    sha.digest().to_string()
+}
+
+fn is_on_disk(book_dir: &Path, path: &str, sha: &str) -> bool {
+    fs::read(book_dir.join(path)).is_ok_and(|contents| blob_sha(&contents) == sha)
+}
+
+/// Whether the book in `book_dir` is complete and none of its files changed.
+pub fn is_intact(book_dir: &Path) -> bool {
+    Manifest::read(book_dir).verify(book_dir)
+}
+
+/// Writes the manifest for files put in `book_dir` some other way than
+/// downloading them, so the next update only downloads what differs.
+pub fn record_files(book_dir: &Path, paths: &[String]) -> Result<()> {
+    let mut files = BTreeMap::new();
+    for path in paths {
+        let contents = fs::read(book_dir.join(path))?;
+        files.insert(path.clone(), blob_sha(&contents));
+    }
+
+    let manifest = Manifest {
+        // Unknown, the files tell what to update.
+        commit: String::new(),
+        files,
+    };
+    manifest.write(book_dir)
+}
+
+/// A hidden directory next to `dir` for work in progress.
+pub fn sibling(dir: &Path, purpose: &str) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    dir.with_file_name(format!(".{}.{}", name, purpose))
+}
+
+/// Puts the complete `staging` directory in the place of `dir`. The old
+/// one is only deleted once the new one is in place.
+pub fn replace_dir(dir: &Path, staging: &Path) -> Result<()> {
+    let old = sibling(dir, "old");
+    let _ = fs::remove_dir_all(&old);
+
+    if dir.exists() {
+        fs::rename(dir, &old)?;
+    }
+    if let Err(err) = fs::rename(staging, dir) {
+        let _ = fs::rename(&old, dir);
+        return Err(err.into());
+    }
+
+    let _ = fs::remove_dir_all(&old);
+    Ok(())
+}
+
+/// What `update_the_book` did.
+pub struct Update {
+    pub commit: String,
+    pub downloaded: usize,
+    pub removed: usize,
+}
+
+struct GitHub {
+    client: Client,
+    headers: HeaderMap,
+}
+
+impl GitHub {
+    fn new() -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", "the-book-tui".parse().unwrap());
+        if let Ok(token) = env::var(TOKEN_ENV_VAR) {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token.trim()))
+                .map_err(|_| format!("{} is not a valid token", TOKEN_ENV_VAR))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        Ok(Self {
+            client: Client::new(),
+            headers,
+        })
+    }
+
+    /// Sends a GET request, trying again after network and server errors
+    /// and waiting for GitHub's rate limit if it is used up. Any other
+    /// response, successful or not, is for the caller to check.
+    fn get(&self, url: &str) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let response = self.client.get(url).headers(self.headers.clone()).send();
+
+            let retry_in = match &response {
+                Ok(response) => {
+                    match rate_limit_wait(response.status(), response.headers(), SystemTime::now())
+                    {
+                        Some(wait) if wait > MAX_RATE_LIMIT_WAIT => {
+                            return Err(format!(
+                                "GitHub's rate limit is used up for the next {} minutes. \
+                             Set {} to a GitHub token for a higher limit.",
+                                wait.as_secs() / 60 + 1,
+                                TOKEN_ENV_VAR
+                            )
+                            .into())
+                        }
+                        Some(wait) => Some(wait),
+                        None if response.status().is_server_error() => Some(backoff(attempt)),
+                        None => None,
+                    }
+                }
+                Err(_) => Some(backoff(attempt)),
+            };
+            let retry_in = match retry_in {
+                Some(retry_in) => retry_in,
+                None => return Ok(response?),
+            };
+
+            if attempt == MAX_ATTEMPTS {
+                return match response {
+                    Ok(response) => Err(format!("{} answered {}", url, response.status()).into()),
+                    Err(err) => Err(err.into()),
+                };
+            }
+            thread::sleep(retry_in);
+        }
+    }
+
+    fn latest_commit(&self, edition: &str) -> Result<GitHubCommit> {
+        let url = format!(
+            "https://api.github.com/repos/{}/commits/{}",
+            REPOSITORY, edition
+        );
+        let response = self.get(&url)?;
+        if !response.status().is_success() {
+            return Err(format!("Could not find the {} edition of the book", edition).into());
+        }
+        Ok(response.json::<GitHubCommit>()?)
+    }
+
+    fn tree(&self, sha: &str) -> Result<Vec<GitHubFile>> {
+        let url = format!(
+            "https://api.github.com/repos/{}/git/trees/{}?recursive=1",
+            REPOSITORY, sha
+        );
+        Ok(self
+            .get(&url)?
+            .error_for_status()?
+            .json::<GithubResponse>()?
+            .tree)
+    }
+
+    /// Saves a file of the repository at `commit` under `dir`.
+    fn download_file(&self, commit: &str, file: &GitHubFile, dir: &Path) -> Result<()> {
+        let url = format!(
+            "https://raw.githubusercontent.com/{}/{}/{}",
+            REPOSITORY, commit, file.path
+        );
+        let contents = self.get(&url)?.error_for_status()?.bytes()?;
+
+        // A truncated or mangled download doesn't get written.
+        if blob_sha(&contents) != file.sha {
+            return Err("the contents don't match the repository's".into());
+        }
+
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    /// Downloads `files` into `dir` a few at a time. Stops at the first
+    /// file that can't be downloaded, even after retrying.
+    fn download_all(
+        &self,
+        commit: &str,
+        files: &[&GitHubFile],
+        dir: &Path,
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        let next = AtomicUsize::new(0);
+        let error: Mutex<Option<String>> = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for _ in 0..WORKERS.min(files.len()) {
+                scope.spawn(|| {
+                    while error.lock().unwrap().is_none() {
+                        let file = match files.get(next.fetch_add(1, Ordering::SeqCst)) {
+                            Some(file) => file,
+                            None => break,
+                        };
+                        match self.download_file(commit, file, dir) {
+                            Ok(()) => pb.inc(1),
+                            Err(err) => {
+                                let message = format!("Could not download {}: {}", file.path, err);
+                                error.lock().unwrap().get_or_insert(message);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        match error.into_inner().unwrap() {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How long after `now` GitHub takes requests again if a response
+/// says the rate limit is used up, None if it isn't.
+fn rate_limit_wait(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+
+    if let Some(seconds) = header("retry-after") {
+        return Some(Duration::from_secs(seconds));
+    }
+    if header("x-ratelimit-remaining") == Some(0) {
+        let reset = header("x-ratelimit-reset")?;
+        let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+    }
+    None
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BACKOFF * 2u32.pow(attempt - 1)
+}
+
+/// Brings the book in `book_dir` to the latest commit of `edition`, a
+/// branch, tag or commit of the repository. Only the chapters and the
+/// code listings they include that changed since the last time are
+/// downloaded, laid out like the repository so the include paths resolve.
+///
+/// The new version is put together next to the old one, which is only
+/// replaced once everything was downloaded. What a failed update got is
+/// kept there, so the next one only downloads the rest.
+pub fn update_the_book(book_dir: &Path, edition: &str) -> Result<Update> {
+    let github = GitHub::new()?;
+    let manifest = Manifest::read(book_dir);
+
+    let latest = github.latest_commit(edition)?;
+    if latest.sha == manifest.commit && manifest.verify(book_dir) {
+        return Ok(Update {
+            commit: latest.sha,
+            downloaded: 0,
+            removed: 0,
+        });
+    }
+
+    let git_tree = github.tree(&latest.commit.tree.sha)?;
+
+    let staging = sibling(book_dir, "download");
+    let update = download_into(
+        &github,
+        &latest.sha,
+        &git_tree,
+        &manifest,
+        book_dir,
+        &staging,
+    )?;
+    replace_dir(book_dir, &staging)?;
+    Ok(update)
+}
+
+/// Fills `staging` with the book at `commit`, copying the files
+/// that are already in `book_dir` or left in `staging` before.
+/// Anything else left in `staging` is deleted.
+fn download_into(
+    github: &GitHub,
+    commit: &str,
+    git_tree: &[GitHubFile],
+    manifest: &Manifest,
+    book_dir: &Path,
+    staging: &Path,
+) -> Result<Update> {
+    let chapters: Vec<&GitHubFile> = git_tree
+        .iter()
+        .filter(|f| f.path.starts_with("src/") && f.path.ends_with(".md"))
+        .collect();
+
+    let changed = copy_unchanged(&chapters, book_dir, staging)?;
+    let pb = ProgressBar::new(changed.len() as u64);
+    github.download_all(commit, &changed, staging, &pb)?;
+    let mut downloaded = changed.len();
+
+    let mut listings: Vec<PathBuf> = Vec::new();
+    for f in chapters.iter() {
+        let markdown = fs::read_to_string(staging.join(&f.path))?;
+        let dir = Path::new(&f.path).parent().unwrap_or(Path::new(""));
+        for path in include::included_paths(&markdown, dir) {
+            if !listings.contains(&path) {
+                listings.push(path);
+            }
+        }
+    }
+
+    // Only what the chapters include is needed, not every listing.
+    let listings: Vec<&GitHubFile> = git_tree
+        .iter()
+        .filter(|f| listings.contains(&PathBuf::from(&f.path)))
+        .collect();
+
+    let changed = copy_unchanged(&listings, book_dir, staging)?;
+    pb.inc_length(changed.len() as u64);
+    github.download_all(commit, &changed, staging, &pb)?;
+    downloaded += changed.len();
+
+    pb.finish();
+
+    let files: BTreeMap<String, String> = chapters
+        .iter()
+        .chain(listings.iter())
+        .map(|f| (f.path.clone(), f.sha.clone()))
+        .collect();
+
+    // Whatever is no longer in the book is left behind with the old directory.
+    let removed = manifest
+        .files
+        .keys()
+        .filter(|path| !files.contains_key(*path))
+        .count();
+
+    fs::create_dir_all(staging)?;
+    remove_others(staging, staging, &files)?;
+    let manifest = Manifest {
+        commit: commit.to_string(),
+        files,
+    };
+    manifest.write(staging)?;
+
+    Ok(Update {
+        commit: commit.to_string(),
+        downloaded,
+        removed,
+    })
+}
+
+/// Copies the `files` that `book_dir` already has as they are in the
+/// repository into `staging`, and returns the ones neither of them has.
+fn copy_unchanged<'a>(
+    files: &[&'a GitHubFile],
+    book_dir: &Path,
+    staging: &Path,
+) -> Result<Vec<&'a GitHubFile>> {
+    let mut changed = Vec::new();
+    for f in files {
+        if is_on_disk(staging, &f.path, &f.sha) {
+            continue;
+        }
+        if !is_on_disk(book_dir, &f.path, &f.sha) {
+            changed.push(*f);
+            continue;
+        }
+
+        let target = staging.join(&f.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(book_dir.join(&f.path), target)?;
+    }
+    Ok(changed)
+}
+
+/// Deletes the files under `dir` that aren't in `files`, by their path
+/// relative to `root`, and the directories that are left empty. A failed
+/// update of another commit can have left them in the staging directory.
+fn remove_others(root: &Path, dir: &Path, files: &BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_others(root, &path, files)?;
+            // Fails for directories that still have files, which is fine.
+            let _ = fs::remove_dir(&path);
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(root)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !files.contains_key(&relative) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests run in parallel, so each gets its own `dir`.
+    fn temp_dir(dir: &str) -> PathBuf {
+        let dir = env::temp_dir().join("the-book-tui-download").join(dir);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, path: &str, contents: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn file(path: &str, contents: &str) -> GitHubFile {
+        GitHubFile {
+            path: path.to_string(),
+            sha: blob_sha(contents.as_bytes()),
+        }
+    }
+
+    fn paths(files: &[&GitHubFile]) -> Vec<String> {
+        files.iter().map(|f| f.path.clone()).collect()
+    }
+
+    #[test]
+    fn blob_sha_is_the_one_git_computes() {
+        assert_eq!(blob_sha(b""), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+        assert_eq!(
+            blob_sha(b"hello world\n"),
+            "3b18e512dba79e4c8300dd08aeb37f8e728b8dad"
+        );
+    }
+
+    #[test]
+    fn rate_limits_are_waited_for_until_they_reset() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+
+        let retry_after = headers(&[("retry-after", "30")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::TOO_MANY_REQUESTS, &retry_after, now),
+            Some(Duration::from_secs(30))
+        );
+
+        let used_up = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1120"),
+        ]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &used_up, now),
+            Some(Duration::from_secs(121))
+        );
+
+        let already_reset =
+            headers(&[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "900")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &already_reset, now),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn other_responses_are_not_rate_limits() {
+        let now = SystemTime::now();
+        let mut remaining = HeaderMap::new();
+        remaining.insert("x-ratelimit-remaining", "10".parse().unwrap());
+        let mut retry_after = HeaderMap::new();
+        retry_after.insert("retry-after", "30".parse().unwrap());
+
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &remaining, now),
+            None
+        );
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &HeaderMap::new(), now),
+            None
+        );
+        assert_eq!(rate_limit_wait(StatusCode::OK, &retry_after, now), None);
+        assert_eq!(
+            rate_limit_wait(StatusCode::BAD_GATEWAY, &retry_after, now),
+            None
+        );
+    }
+
+    #[test]
+    fn unchanged_files_are_copied_and_the_rest_returned() {
+        let dir = temp_dir("copy");
+        let (book_dir, staging) = (dir.join("book"), dir.join("staging"));
+        write(&book_dir, "src/same.md", "same");
+        write(&book_dir, "src/changed.md", "old");
+        write(&staging, "src/staged.md", "staged");
+
+        let files = [
+            file("src/same.md", "same"),
+            file("src/changed.md", "new"),
+            file("src/staged.md", "staged"),
+            file("src/new.md", "new"),
+        ];
+        let files = files.iter().collect::<Vec<&GitHubFile>>();
+        let changed = copy_unchanged(&files, &book_dir, &staging).unwrap();
+
+        assert_eq!(paths(&changed), ["src/changed.md", "src/new.md"]);
+        assert_eq!(
+            fs::read_to_string(staging.join("src/same.md")).unwrap(),
+            "same"
+        );
+        assert!(!staging.join("src/changed.md").exists());
+    }
+
+    #[test]
+    fn staging_left_by_another_commit_is_reused_and_pruned() {
+        let dir = temp_dir("staging");
+        let (book_dir, staging) = (dir.join("book"), dir.join("book-staging"));
+        write(
+            &book_dir,
+            "src/ch01.md",
+            "# One\n{{#include ../listings/one.rs}}\n",
+        );
+        write(&book_dir, "listings/one.rs", "fn one() {}");
+        // What a failed update to another commit got before.
+        write(&staging, "src/ch02.md", "# Two\n");
+        write(&staging, "src/removed.md", "# Removed\n");
+        write(&staging, "listings/old/two.rs", "fn two() {}");
+        write(&staging, MANIFEST, "{}");
+
+        let tree = [
+            file("src/ch01.md", "# One\n{{#include ../listings/one.rs}}\n"),
+            file("src/ch02.md", "# Two\n"),
+            file("listings/one.rs", "fn one() {}"),
+            file("listings/unused.rs", "fn unused() {}"),
+        ];
+        let github = GitHub::new().unwrap();
+        let update = download_into(
+            &github,
+            "abc",
+            &tree,
+            &Manifest::read(&book_dir),
+            &book_dir,
+            &staging,
+        )
+        .unwrap();
+        assert_eq!(update.downloaded, 0);
+
+        replace_dir(&book_dir, &staging).unwrap();
+        assert!(is_intact(&book_dir));
+        assert_eq!(Manifest::read(&book_dir).commit, "abc");
+        assert!(!book_dir.join("src/removed.md").exists());
+        assert!(!book_dir.join("listings/old").exists());
+        assert!(!book_dir.join("listings/unused.rs").exists());
+        assert!(!staging.exists());
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Includes can include other files, this stops include loops.
+const MAX_DEPTH: usize = 10;
+
+/// Which part of a file an include directive wants.
+enum Selection {
+    All,
+    /// The lines between `ANCHOR: name` and `ANCHOR_END: name`.
+    Anchor(String),
+    /// Zero based, the end is exclusive.
+    Lines(usize, Option<usize>),
+}
+
+/// An mdBook `{{#include}}` or `{{#rustdoc_include}}` directive.
+struct Include {
+    path: PathBuf,
+    selection: Selection,
+    /// rustdoc_include keeps the whole file so the example still
+    /// compiles, the lines outside of the selection are hidden.
+    rustdoc: bool,
+}
+
+impl Include {
+    /// Parses what is between `{{#` and `}}`, like
+    /// `include ../listings/ch02/src/main.rs:here`.
+    fn parse(directive: &str) -> Option<Self> {
+        let (kind, argument) = directive.trim().split_once(char::is_whitespace)?;
+        let rustdoc = match kind {
+            "include" => false,
+            "rustdoc_include" => true,
+            _ => return None,
+        };
+
+        let mut parts = argument.trim().split(':');
+        let path = PathBuf::from(parts.next()?);
+        let selection = match (parts.next(), parts.next()) {
+            (None, _) | (Some(""), None) => Selection::All,
+            (Some(start), None) => match start.parse::<usize>() {
+                // A single number is just that line.
+                Ok(line) => Selection::Lines(line.saturating_sub(1), Some(line)),
+                Err(_) => Selection::Anchor(start.to_string()),
+            },
+            (Some(start), Some(end)) => {
+                let start = match start {
+                    "" => 0,
+                    start => start.parse::<usize>().ok()?.saturating_sub(1),
+                };
+                let end = match end {
+                    "" => None,
+                    end => Some(end.parse::<usize>().ok()?),
+                };
+                Selection::Lines(start, end)
+            }
+        };
+
+        Some(Self {
+            path,
+            selection,
+            rustdoc,
+        })
+    }
+
+    /// Reads the included part of the file, None if it can't be read.
+    fn expand(&self, dir: &Path, depth: usize) -> Option<String> {
+        let path = dir.join(&self.path);
+        let contents = fs::read_to_string(&path).ok()?;
+
+        let mut selected = Vec::new();
+        let mut in_anchor = false;
+        // Anchor lines count for line ranges like any other line.
+        for (number, line) in contents.lines().enumerate() {
+            // Anchor markers are never shown, whichever part is included.
+            if let Some(name) = anchor_name(line, "ANCHOR:") {
+                in_anchor |= self.is_anchor(name);
+                continue;
+            }
+            if let Some(name) = anchor_name(line, "ANCHOR_END:") {
+                in_anchor &= !self.is_anchor(name);
+                continue;
+            }
+
+            let included = match &self.selection {
+                Selection::All => true,
+                Selection::Anchor(_) => in_anchor,
+                Selection::Lines(start, end) => {
+                    number >= *start && end.is_none_or(|end| number < end)
+                }
+            };
+
+            if included {
+                selected.push(line.to_string());
+            } else if self.rustdoc {
+                selected.push(hide(line));
+            }
+        }
+
+        let dir = path.parent().unwrap_or(dir);
+        Some(expand(&selected.join("\n"), dir, depth + 1).contents)
+    }
+
+    fn is_anchor(&self, name: &str) -> bool {
+        matches!(&self.selection, Selection::Anchor(anchor) if anchor == name)
+    }
+}
+
+fn anchor_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(marker)?;
+    rest.trim_start()
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .next()
+}
+
+/// The way mdBook hides lines of Rust code, see `markdown::hidden_line`.
+fn hide(line: &str) -> String {
+    if line.is_empty() {
+        String::from("#")
+    } else {
+        format!("# {}", line)
+    }
+}
+
+/// Contents with their include directives expanded.
+pub struct Expanded {
+    pub contents: String,
+    /// For each line of `contents`, the line of the file it was expanded
+    /// from. Included lines count as the line of their directive.
+    pub source_lines: Vec<usize>,
+}
+
+impl Expanded {
+    /// Adds text of the file itself, which starts on `line`.
+    /// Moves `line` past the lines of `text`.
+    fn push_source(&mut self, text: &str, line: &mut usize) {
+        for _ in text.matches('\n') {
+            *line += 1;
+            self.source_lines.push(*line);
+        }
+        self.contents.push_str(text);
+    }
+
+    /// Adds text included by the directive on `line`.
+    fn push_included(&mut self, text: &str, line: usize) {
+        for _ in text.matches('\n') {
+            self.source_lines.push(line);
+        }
+        self.contents.push_str(text);
+    }
+}
+
+/// Replaces the include directives in `contents` with what they include.
+/// Paths are relative to `dir`, the directory of the markdown file.
+/// Directives that can't be resolved are left as they are.
+pub fn expand_includes(contents: &str, dir: &Path) -> Expanded {
+    expand(contents, dir, 0)
+}
+
+fn expand(contents: &str, dir: &Path, depth: usize) -> Expanded {
+    let mut expanded = Expanded {
+        contents: String::new(),
+        source_lines: vec![0],
+    };
+    let mut line = 0;
+    let mut rest = contents;
+    while let Some((before, directive, after)) = next_directive(rest) {
+        let written = format!("{{{{#{}}}}}", directive);
+        // `\{{#include}}` is how a directive is written without expanding it.
+        if let Some(before) = before.strip_suffix('\\') {
+            expanded.push_source(before, &mut line);
+            expanded.push_source(&written, &mut line);
+        } else {
+            expanded.push_source(before, &mut line);
+            let include = Include::parse(directive)
+                .filter(|_| depth < MAX_DEPTH)
+                .and_then(|include| include.expand(dir, depth));
+            match include {
+                Some(include) => {
+                    expanded.push_included(&include, line);
+                    line += written.matches('\n').count();
+                }
+                None => expanded.push_source(&written, &mut line),
+            }
+        }
+        rest = after;
+    }
+    expanded.push_source(rest, &mut line);
+    expanded
+}
+
+/// Splits `text` into what comes before the first `{{#...}}`,
+/// what is inside of it and what comes after.
+fn next_directive(text: &str) -> Option<(&str, &str, &str)> {
+    let (before, rest) = text.split_once("{{#")?;
+    let (directive, after) = rest.split_once("}}")?;
+    Some((before, directive, after))
+}
+
+/// The files `contents` includes, relative to `dir` and without
+/// any `..` in them so they can be looked up in a repository.
+pub fn included_paths(contents: &str, dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut rest = contents;
+    while let Some((before, directive, after)) = next_directive(rest) {
+        if !before.ends_with('\\') {
+            if let Some(include) = Include::parse(directive) {
+                let path = normalize(&dir.join(&include.path));
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        rest = after;
+    }
+    paths
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "\
+fn main() {
+    // ANCHOR: here
+    let x = 5;
+    // ANCHOR_END: here
+    println!(\"{}\", x);
+}
+";
+
+    /// Tests run in parallel, so each gets its own `dir`.
+    fn listing_dir(dir: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join("the-book-tui-include").join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.rs"), LISTING).unwrap();
+        dir
+    }
+
+    fn expand_listing(dir: &str, directive: &str) -> String {
+        expand_includes(directive, &listing_dir(dir)).contents
+    }
+
+    #[test]
+    fn line_ranges_count_anchor_lines() {
+        assert_eq!(
+            expand_listing("lines", "{{#include main.rs:3:5}}"),
+            "    let x = 5;\n    println!(\"{}\", x);"
+        );
+        assert_eq!(expand_listing("lines", "{{#include main.rs:6}}"), "}");
+    }
+
+    #[test]
+    fn anchors_select_their_lines() {
+        assert_eq!(
+            expand_listing("anchors", "{{#include main.rs:here}}"),
+            "    let x = 5;"
+        );
+        assert_eq!(
+            expand_listing("anchors", "{{#rustdoc_include main.rs:here}}"),
+            "# fn main() {\n    let x = 5;\n#     println!(\"{}\", x);\n# }"
+        );
+    }
+
+    #[test]
+    fn escaped_directives_are_kept() {
+        assert_eq!(
+            expand_listing("escaped", "\\{{#include main.rs}}"),
+            "{{#include main.rs}}"
+        );
+    }
+
+    #[test]
+    fn included_lines_map_to_their_directive() {
+        let dir = listing_dir("source-lines");
+        let expanded =
+            expand_includes("# Listing\n```rust\n{{#include main.rs}}\n```\nAfter", &dir);
+
+        assert_eq!(expanded.contents.lines().count(), 8);
+        assert_eq!(expanded.source_lines, [0, 1, 2, 2, 2, 2, 3, 4]);
+    }
+}
@@ -1,6 +1,5 @@
-use book::{MarkdownFile, Result};
-
-use crate::book::read_the_book;
+use book::{Book, MarkdownFile, Result, SearchResult};
+use clap::{Parser, Subcommand};
 use crossterm::{
     cursor,
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
@@ -9,43 +8,69 @@ use crossterm::{
         self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
     },
 };
+use markdown::{MarkdownRenderer, Rendered};
+use source::BookSource;
 
-use std::{io, panic, time::Duration};
+use std::{io, panic, path::PathBuf, time::Duration};
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Direction, Layout},
     style::{Modifier, Style},
-    text::Text,
-    widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap},
+    text::{Span, Spans, Text},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap},
     Frame, Terminal,
 };
 mod book;
+mod bundle;
+mod cli;
+mod download;
+mod include;
+mod markdown;
+mod section;
+mod source;
+mod summary;
+
 struct App {
+    title: String,
     state: TableState,
     items: Vec<MarkdownFile>,
     current_items: Vec<MarkdownFile>,
+    /// What the dock lists while searching, best match first.
+    results: Vec<SearchResult>,
     scroll: u16,
+    /// The line of the chapter to scroll the reader to once
+    /// it is drawn and its width is known.
+    jump_to: Option<usize>,
     search: Vec<char>,
     latest_search: Vec<char>,
+    renderer: MarkdownRenderer,
+    /// The last chapter shown in the reader, rendered. Kept
+    /// since the reader is drawn several times a second.
+    rendered: Option<(PathBuf, Rendered)>,
 }
 
 impl App {
-    fn new() -> Result<Self> {
-        let chapters = read_the_book()?;
-        Ok(App {
+    fn new(book: Book) -> Self {
+        let chapters = book.chapters;
+        App {
+            title: book.title,
             state: TableState::default(),
             items: chapters.clone(),
             scroll: 0,
+            jump_to: None,
             search: Vec::new(),
             current_items: chapters,
+            results: Vec::new(),
             latest_search: Vec::new(),
-        })
+            renderer: MarkdownRenderer::new(),
+            rendered: None,
+        }
     }
 
     pub fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
-                if i < self.current_items.len() - 1 {
+                if i + 1 < self.row_count() {
                     i + 1
                 } else {
                     i
@@ -53,8 +78,7 @@ impl App {
             }
             None => 0,
         };
-        self.state.select(Some(i));
-        self.scroll = 0;
+        self.select(i);
     }
 
     pub fn previous(&mut self) {
@@ -68,18 +92,65 @@ impl App {
             }
             None => 0,
         };
+        self.select(i);
+    }
+
+    /// Whether the dock lists the sections that matched a search. Goes by
+    /// the search the lists were made for, not the one still being typed.
+    fn is_searching(&self) -> bool {
+        self.latest_search.iter().any(|c| !c.is_whitespace())
+    }
+
+    /// The dock lists the chapters, or the matching sections while searching.
+    fn row_count(&self) -> usize {
+        if !self.is_searching() {
+            self.current_items.len()
+        } else {
+            self.results.len()
+        }
+    }
+
+    /// Selects a row of the dock, a search result opens at its section.
+    fn select(&mut self, i: usize) {
         self.state.select(Some(i));
         self.scroll = 0;
+        self.jump_to = if self.is_searching() {
+            self.results.get(i).map(|result| result.line)
+        } else {
+            None
+        };
     }
 
-    pub fn load(&mut self) -> String {
-        match self.state.selected() {
-            Some(i) => match self.current_items.get(i) {
-                Some(x) => x.contents.clone(),
-                None => String::from(""),
-            },
-            None => String::from(""),
+    fn selected_chapter(&self) -> Option<&MarkdownFile> {
+        let i = self.state.selected()?;
+        if !self.is_searching() {
+            self.current_items.get(i)
+        } else {
+            self.current_items.get(self.results.get(i)?.chapter)
+        }
+    }
+
+    /// Renders the selected chapter and scrolls to the
+    /// section to jump to, if any, for a reader `width` wide.
+    pub fn load(&mut self, width: u16) -> Text<'static> {
+        let chapter = match self.selected_chapter() {
+            Some(x) => x,
+            None => return Text::default(),
+        };
+
+        let rendered = match &self.rendered {
+            Some((path, rendered)) if *path == chapter.path => rendered.clone(),
+            _ => {
+                let rendered = self.renderer.render(&chapter.contents);
+                self.rendered = Some((chapter.path.clone(), rendered.clone()));
+                rendered
+            }
+        };
+
+        if let Some(line) = self.jump_to.take() {
+            self.scroll = rendered.scroll_to(line, width);
         }
+        rendered.text
     }
 
     pub fn scroll_down(&mut self) {
@@ -95,18 +166,12 @@ impl App {
 
         if search_phrase.is_empty() {
             self.current_items = self.items.clone();
+            self.results.clear();
             return;
         }
 
-        self.current_items = self
-            .items
-            .iter_mut()
-            .map(|md_file| md_file.clone().search(&search_phrase))
-            .filter(|md_file| md_file.relevancy > 0)
-            .collect();
-
-        self.current_items
-            .sort_by(|a, b| b.relevancy.cmp(&a.relevancy));
+        self.current_items = book::search_chapters(&self.items, &search_phrase);
+        self.results = book::search_sections(&self.current_items, &search_phrase);
     }
 }
 
@@ -139,7 +204,7 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<(
         } else if app.search != app.latest_search {
             app.latest_search = app.search.clone();
             app.search(app.search.iter().collect());
-            app.state.select(Some(0));
+            app.select(0);
         }
     }
 }
@@ -157,15 +222,37 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     let mut directory_rows = vec![];
 
-    app.current_items.iter().for_each(|chapter| {
-        let title = chapter.title.clone();
-        directory_rows.push(Row::new(vec![title]))
-    });
+    if !app.is_searching() {
+        app.current_items.iter().for_each(|chapter| {
+            let indent = "  ".repeat(chapter.level);
+            let title = format!("{}{}", indent, chapter.numbered_title());
+            directory_rows.push(Row::new(vec![title]))
+        });
+    } else {
+        app.results.iter().for_each(|result| {
+            let chapter = &app.current_items[result.chapter];
+            let title = match &result.heading {
+                Some(heading) => format!("{} › {}", chapter.title, heading),
+                None => chapter.title.clone(),
+            };
+            let text = Text::from(vec![
+                Spans::from(Span::styled(
+                    title,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Spans::from(Span::styled(
+                    format!("  {}", result.snippet),
+                    Style::default().add_modifier(Modifier::DIM),
+                )),
+            ]);
+            directory_rows.push(Row::new(vec![Cell::from(text)]).height(2))
+        });
+    }
 
-    let directory_title = if !app.search.is_empty() {
-        "Directory (sorted by search relevancy)"
+    let directory_title = if app.is_searching() {
+        format!("{} (sections sorted by search relevancy)", app.title)
     } else {
-        "Directory"
+        app.title.clone()
     };
 
     let directory_table = Table::new(directory_rows)
@@ -177,7 +264,9 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .widths(&[Constraint::Percentage(100)])
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
 
-    let page = Paragraph::new(app.load())
+    // The reader's borders take a column on each side.
+    let reader_width = main_layout[1].width.saturating_sub(2);
+    let page = Paragraph::new(app.load(reader_width))
         .block(Block::default().title("Reader").borders(Borders::ALL))
         .scroll((app.scroll, 0))
         .wrap(Wrap { trim: false });
@@ -217,36 +306,73 @@ fn setup_panic_hook() {
     }));
 }
 
-// #[derive(Parser, Debug)]
-// struct Args {
-//     /// String to search in the book
-//     needle: Option<String>,
-//     // TODO: Factor out common code and make the app
-//     // be able to open other books
-//     // book: String,
-// }
+#[derive(Parser, Debug)]
+struct Args {
+    /// An mdBook directory or the URL of a git repository
+    /// with one. Opens The Rust Book if not given.
+    #[arg(short, long, global = true)]
+    book: Option<String>,
+    /// A branch, tag or commit of The Rust Book's repository.
+    /// Each edition is kept in its own directory.
+    #[arg(short, long, global = true, conflicts_with = "book")]
+    edition: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// Without a command the book is opened in the TUI.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the sections that match a search
+    Search {
+        query: String,
+        /// How many sections to print
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the markdown of a chapter
+    Show {
+        /// The chapter's number like 4.1, its title or its file name
+        chapter: String,
+    },
+    /// Download what changed in the book since it was last downloaded
+    Update,
+    /// Read The Rust Book from a tarball made by export or a checkout
+    /// of its repository, without going online
+    Import {
+        /// A tarball or a directory
+        from: PathBuf,
+    },
+    /// Pack The Rust Book into a tarball to import on another machine
+    Export { tarball: PathBuf },
+}
 
 fn main() -> Result<()> {
     better_panic::install();
 
-    // let args = Args::parse();
-
-    // if args.needle.is_some() {
-    //     let mut book = read_the_book()?;
-    //     let needle = args.needle.unwrap().trim().to_lowercase();
-
-    //     let mut relevant_pages = book
-    //         .iter_mut()
-    //         .map(|md_file| md_file.clone().search(&needle))
-    //         .filter(|md_file| md_file.relevancy > 0)
-    //         .collect::<Vec<MarkdownFile>>();
+    let args = Args::parse();
+    let source = BookSource::from_arg(args.book.as_deref(), args.edition.as_deref())?;
+    match &args.command {
+        Some(Command::Update) => return source.update(),
+        Some(Command::Import { from }) => return bundle::import(&source, from),
+        Some(Command::Export { tarball }) => return bundle::export(&source, tarball),
+        _ => {}
+    }
+    let book = book::read_book(&source)?;
 
-    //     relevant_pages.sort_by(|a, b| b.relevancy.cmp(&a.relevancy));
-    //     let most_relevant_page = relevant_pages.first().unwrap();
-    //     let most_relevant_paragraph = most_relevant_page.paragraphs.first().unwrap();
+    match args.command {
+        Some(Command::Search { query, limit, json }) => {
+            return cli::search(&book, &query, limit, json, &mut io::stdout().lock())
+        }
+        Some(Command::Show { chapter }) => {
+            return cli::show(&book, &chapter, &mut io::stdout().lock())
+        }
+        _ => {}
+    }
 
-    //     println!("{}", most_relevant_page.contents.clone());
-    // } else {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
 
@@ -257,7 +383,7 @@ fn main() -> Result<()> {
 
     setup_panic_hook();
 
-    let app = App::new()?;
+    let app = App::new(book);
     let res = run_app(&mut terminal, app);
 
     disable_raw_mode()?;
@@ -271,7 +397,6 @@ fn main() -> Result<()> {
     if let Err(err) = res {
         println!("{:?}", err)
     }
-    // }
 
     Ok(())
 }
@@ -0,0 +1,575 @@
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+};
+
+/// One of syntect's bundled themes, made for dark backgrounds
+/// like the one of most terminals.
+const CODE_THEME: &str = "base16-ocean.dark";
+
+/// Turns the markdown of a chapter into styled text for the reader.
+pub struct MarkdownRenderer {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl MarkdownRenderer {
+    pub fn new() -> Self {
+        let themes = ThemeSet::load_defaults();
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes[CODE_THEME].clone(),
+        }
+    }
+
+    pub fn render(&self, markdown: &str) -> Rendered {
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        let mut writer = Writer::new(self);
+        let mut headings = Vec::new();
+        for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+            let heading = matches!(event, Event::Start(Tag::Heading(..)));
+            writer.event(event);
+            if heading {
+                let line = markdown[..range.start].matches('\n').count();
+                headings.push((line, writer.lines.len()));
+            }
+        }
+        writer.flush();
+        Rendered {
+            text: Text::from(writer.lines),
+            headings,
+        }
+    }
+}
+
+/// A chapter ready to be shown in the reader.
+#[derive(Clone)]
+pub struct Rendered {
+    pub text: Text<'static>,
+    /// Where each heading is, as the line in the
+    /// markdown and the line in the text.
+    headings: Vec<(usize, usize)>,
+}
+
+impl Rendered {
+    /// How far to scroll the reader, `width` wide, to show the section
+    /// that starts on `line` of the markdown at the top. Lines are
+    /// taken to wrap at `width`, words going over it can add a few.
+    pub fn scroll_to(&self, line: usize, width: u16) -> u16 {
+        let start = self
+            .headings
+            .iter()
+            .take_while(|(markdown_line, _)| *markdown_line <= line)
+            .last()
+            .map_or(0, |(_, text_line)| *text_line);
+
+        let width = width.max(1) as usize;
+        let wrapped = self.text.lines[..start.min(self.text.lines.len())]
+            .iter()
+            .map(|line| line.width().max(1).div_ceil(width))
+            .sum::<usize>();
+        wrapped.min(u16::MAX as usize) as u16
+    }
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    /// How many of the rows are the header.
+    head_rows: usize,
+}
+
+/// Builds the lines while walking through the markdown events.
+struct Writer<'r> {
+    renderer: &'r MarkdownRenderer,
+    lines: Vec<Spans<'static>>,
+    line: Vec<Span<'static>>,
+    /// Nested inline styles, e.g. emphasis inside a link.
+    styles: Vec<Style>,
+    /// The next number of each nested list, None for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// The marker of a list item that hasn't been written yet.
+    marker: Option<String>,
+    quotes: usize,
+    /// What the info string says about the code block
+    /// being read, and its text.
+    code: Option<(CodeInfo, String)>,
+    table: Option<Table>,
+}
+
+impl<'r> Writer<'r> {
+    fn new(renderer: &'r MarkdownRenderer) -> Self {
+        Self {
+            renderer,
+            lines: Vec::new(),
+            line: Vec::new(),
+            styles: vec![Style::default()],
+            lists: Vec::new(),
+            marker: None,
+            quotes: 0,
+            code: None,
+            table: None,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => {
+                let style = self.style().fg(Color::Yellow);
+                self.push(&code, style);
+            }
+            Event::Html(html) => {
+                let text = strip_tags(&html);
+                if !text.trim().is_empty() {
+                    self.text(text.trim_end_matches('\n'));
+                }
+                // Block level HTML comes with its line break.
+                if html.ends_with('\n') {
+                    self.flush();
+                }
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.flush(),
+            Event::Rule => {
+                self.flush();
+                self.lines.push(Spans::from("─".repeat(40)));
+                self.blank_line();
+            }
+            Event::TaskListMarker(done) => self.text(if done { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(name) => self.text(&format!("[{}]", name)),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(level, _, _) => {
+                self.flush();
+                let style = match level {
+                    HeadingLevel::H1 => Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                    HeadingLevel::H2 => Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                    _ => Style::default().add_modifier(Modifier::BOLD),
+                };
+                self.styles.push(style);
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quotes += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => CodeInfo::parse(&info),
+                    CodeBlockKind::Indented => CodeInfo::default(),
+                };
+                self.code = Some((info, String::new()));
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => String::from("• "),
+                };
+                self.marker = Some(marker);
+            }
+            Tag::Table(_) => {
+                self.flush();
+                self.table = Some(Table::default());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::TableCell => {
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(String::new());
+                }
+            }
+            Tag::Emphasis => self
+                .styles
+                .push(self.style().add_modifier(Modifier::ITALIC)),
+            Tag::Strong => self.styles.push(self.style().add_modifier(Modifier::BOLD)),
+            Tag::Strikethrough => self
+                .styles
+                .push(self.style().add_modifier(Modifier::CROSSED_OUT)),
+            Tag::Link(..) => self.styles.push(
+                self.style()
+                    .fg(Color::Blue)
+                    .add_modifier(Modifier::UNDERLINED),
+            ),
+            Tag::Image(..) => self.text("[image: "),
+            Tag::Paragraph | Tag::FootnoteDefinition(_) => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                self.flush();
+                // Items of tight lists have no paragraphs, loose ones get
+                // a blank line between items like the rest of the text.
+                self.blank_line();
+            }
+            Tag::Heading(..) => {
+                self.styles.pop();
+                self.flush();
+                self.blank_line();
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quotes -= 1;
+            }
+            Tag::CodeBlock(_) => {
+                if let Some((info, code)) = self.code.take() {
+                    self.code_block(&info, &code);
+                }
+                self.blank_line();
+            }
+            Tag::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            }
+            Tag::Item => self.flush(),
+            Tag::Table(_) => {
+                if let Some(table) = self.table.take() {
+                    self.write_table(table);
+                }
+                self.blank_line();
+            }
+            Tag::TableHead => {
+                if let Some(table) = &mut self.table {
+                    table.head_rows = table.rows.len();
+                }
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) => {
+                self.styles.pop();
+            }
+            Tag::Image(..) => self.text("]"),
+            Tag::TableRow | Tag::TableCell | Tag::FootnoteDefinition(_) => {}
+        }
+    }
+
+    fn style(&self) -> Style {
+        self.styles.last().copied().unwrap_or_default()
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some((_, code)) = &mut self.code {
+            code.push_str(text);
+            return;
+        }
+        self.push(text, self.style());
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        if let Some(cell) = self
+            .table
+            .as_mut()
+            .and_then(|t| t.rows.last_mut())
+            .and_then(|row| row.last_mut())
+        {
+            cell.push_str(text);
+            return;
+        }
+        if self.line.is_empty() {
+            let prefix = self.prefix();
+            self.line.push(Span::raw(prefix));
+        }
+        self.line.push(Span::styled(text.to_string(), style));
+    }
+
+    /// What goes in front of every line: a bar for each block
+    /// quote and indentation for each list the line is in.
+    fn prefix(&mut self) -> String {
+        let mut prefix = "│ ".repeat(self.quotes);
+        let depth = self.lists.len();
+        match self.marker.take() {
+            Some(marker) => {
+                prefix.push_str(&"   ".repeat(depth.saturating_sub(1)));
+                prefix.push_str(&marker);
+            }
+            None => prefix.push_str(&"   ".repeat(depth)),
+        }
+        prefix
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.lines.push(Spans::from(line));
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if self.lines.last().is_some_and(|l| l.width() > 0) {
+            self.lines.push(Spans::default());
+        }
+    }
+
+    fn code_block(&mut self, info: &CodeInfo, code: &str) {
+        let syntaxes = &self.renderer.syntaxes;
+        let mut highlighter = syntaxes
+            .find_syntax_by_token(&info.language)
+            .map(|syntax| HighlightLines::new(syntax, &self.renderer.theme));
+
+        // The syntaxes are loaded for lines with their `\n`.
+        for line in LinesWithEndings::from(code) {
+            let line = match (info.rust, hidden_line(line)) {
+                (true, Some(line)) => match line {
+                    Some(line) => line,
+                    None => continue,
+                },
+                _ => line,
+            };
+
+            let mut spans = vec![Span::raw(self.prefix())];
+            let highlighted = highlighter
+                .as_mut()
+                .and_then(|h| h.highlight_line(line, syntaxes).ok());
+            match highlighted {
+                Some(ranges) => spans.extend(ranges.into_iter().map(|(style, text)| {
+                    Span::styled(text.trim_end_matches('\n').to_string(), code_style(style))
+                })),
+                None => spans.push(Span::styled(
+                    line.trim_end_matches('\n').to_string(),
+                    Style::default().fg(Color::Green),
+                )),
+            }
+            self.lines.push(Spans::from(spans));
+        }
+    }
+
+    fn write_table(&mut self, table: Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths = (0..columns)
+            .map(|i| {
+                table
+                    .rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<usize>>();
+
+        for (i, row) in table.rows.iter().enumerate() {
+            let cells = widths
+                .iter()
+                .enumerate()
+                .map(|(j, width)| {
+                    let cell = row.get(j).map(String::as_str).unwrap_or_default();
+                    format!("{:width$}", cell, width = width)
+                })
+                .collect::<Vec<String>>();
+
+            let style = if i < table.head_rows {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let prefix = self.prefix();
+            self.lines.push(Spans::from(vec![
+                Span::raw(prefix),
+                Span::styled(cells.join(" │ "), style),
+            ]));
+
+            if i + 1 == table.head_rows {
+                let rule = widths
+                    .iter()
+                    .map(|width| "─".repeat(*width))
+                    .collect::<Vec<String>>()
+                    .join("─┼─");
+                let prefix = self.prefix();
+                self.lines.push(Spans::from(format!("{}{}", prefix, rule)));
+            }
+        }
+    }
+}
+
+/// Attributes rustdoc and mdBook take from the info string of a block
+/// of Rust code, besides `editionXXXX`. The book adds some of its own
+/// to mark listings that don't compile or panic.
+const RUST_ATTRIBUTES: [&str; 13] = [
+    "ignore",
+    "no_run",
+    "should_panic",
+    "compile_fail",
+    "test_harness",
+    "standalone_crate",
+    "editable",
+    "noplayground",
+    "noplaypen",
+    "mdbook-runnable",
+    "does_not_compile",
+    "panics",
+    "not_desired_behavior",
+];
+
+/// What the info string of a fenced code block, like
+/// `rust,ignore` or `console`, says about the block.
+#[derive(Default)]
+struct CodeInfo {
+    /// The token syntect finds the syntax by, empty for none.
+    language: String,
+    /// Rust code has the lines mdBook hides, see `hidden_line`.
+    rust: bool,
+}
+
+impl CodeInfo {
+    /// The words of the info string are separated by commas or spaces.
+    /// A block that starts with an attribute, like `ignore`, is Rust
+    /// as far as rustdoc is concerned.
+    fn parse(info: &str) -> Self {
+        let mut words = info
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty());
+
+        match words.next() {
+            Some(word) if word == "rust" || is_rust_attribute(word) => Self {
+                language: String::from("rust"),
+                rust: true,
+            },
+            Some(word) => Self {
+                language: word.to_string(),
+                rust: false,
+            },
+            None => Self::default(),
+        }
+    }
+}
+
+fn is_rust_attribute(word: &str) -> bool {
+    RUST_ATTRIBUTES.contains(&word) || word.starts_with("edition")
+}
+
+/// mdBook hides lines of Rust code starting with `# `, they are
+/// only there to make the example compile. `##` escapes a `#`.
+/// Returns None for lines that aren't affected, Some(None)
+/// for hidden ones and Some(line) for escaped ones.
+fn hidden_line(line: &str) -> Option<Option<&str>> {
+    let trimmed = line.trim();
+    if trimmed == "#" || trimmed.starts_with("# ") {
+        return Some(None);
+    }
+    if trimmed.starts_with("##") {
+        let indent = line.len() - line.trim_start().len();
+        return Some(Some(&line[indent + 1..]));
+    }
+    None
+}
+
+/// Keeps the text of inline HTML like `<span class="filename">`.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Code keeps the reader's background, a block in the
+/// theme's own colour would stand out from the chapter.
+fn code_style(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let modifiers = [
+        (FontStyle::BOLD, Modifier::BOLD),
+        (FontStyle::ITALIC, Modifier::ITALIC),
+    ]
+    .into_iter()
+    .filter(|(font_style, _)| style.font_style.contains(*font_style))
+    .fold(Modifier::empty(), |all, (_, modifier)| all | modifier);
+
+    Style::default()
+        .fg(Color::Rgb(fg.r, fg.g, fg.b))
+        .add_modifier(modifiers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &Text) -> Vec<String> {
+        text.lines
+            .iter()
+            .map(|spans| spans.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn rust_code_hides_mdbook_lines() {
+        let markdown =
+            "```rust,ignore\n# fn main() {\nlet x = 5;\n##[derive(Debug)]\n#\n# }\n```\n";
+        let rendered = MarkdownRenderer::new().render(markdown);
+        assert_eq!(
+            lines(&rendered.text),
+            ["let x = 5;", "#[derive(Debug)]", ""]
+        );
+    }
+
+    #[test]
+    fn code_in_other_languages_is_kept() {
+        let markdown = "```console\n# cargo build\n```\n";
+        let rendered = MarkdownRenderer::new().render(markdown);
+        assert_eq!(lines(&rendered.text)[0], "# cargo build");
+    }
+
+    #[test]
+    fn hidden_lines_follow_any_rust_attributes() {
+        for info in [
+            "rust,no_run",
+            "rust ignore",
+            "rust,edition2021",
+            "should_panic",
+        ] {
+            let markdown = format!("```{}\n# fn main() {{\nlet x = 5;\n# }}\n```\n", info);
+            let rendered = MarkdownRenderer::new().render(&markdown);
+            assert_eq!(lines(&rendered.text), ["let x = 5;", ""], "{}", info);
+        }
+    }
+
+    #[test]
+    fn info_strings_give_the_language_before_the_attributes() {
+        let info = CodeInfo::parse("rust, does_not_compile");
+        assert_eq!((info.language.as_str(), info.rust), ("rust", true));
+
+        let info = CodeInfo::parse("ignore");
+        assert_eq!((info.language.as_str(), info.rust), ("rust", true));
+
+        let info = CodeInfo::parse("console");
+        assert_eq!((info.language.as_str(), info.rust), ("console", false));
+
+        let info = CodeInfo::parse("");
+        assert_eq!((info.language.as_str(), info.rust), ("", false));
+    }
+}
@@ -0,0 +1,201 @@
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag};
+use std::ops::Range;
+
+/// A part of a markdown file under a heading, with the
+/// sections under its subheadings as children.
+#[derive(Clone, Debug)]
+pub struct Section {
+    /// None for the root, which is the whole file.
+    pub heading: Option<String>,
+    /// 1 to 6 like `#` to `######`, 0 for the root.
+    pub level: usize,
+    /// Where the section is in the file, from the start of its
+    /// heading to the next heading of the same level or higher.
+    pub range: Range<usize>,
+    /// The line its heading is on, counting from 0.
+    pub line: usize,
+    pub children: Vec<Section>,
+}
+
+impl Section {
+    /// The section's own text, without its subsections.
+    pub fn own_range(&self) -> Range<usize> {
+        let end = self
+            .children
+            .first()
+            .map_or(self.range.end, |child| child.range.start);
+        self.range.start..end
+    }
+
+    /// This section and all the ones under it, in the order of the file.
+    pub fn flatten(&self) -> Vec<&Section> {
+        let mut sections = vec![self];
+        for child in &self.children {
+            sections.extend(child.flatten());
+        }
+        sections
+    }
+}
+
+/// Reads the headings of `contents` into a tree. Only real headings
+/// count, a `#` line in a code block is just code.
+pub fn parse_sections(contents: &str) -> Section {
+    let root = Section {
+        heading: None,
+        level: 0,
+        range: 0..contents.len(),
+        line: 0,
+        children: Vec::new(),
+    };
+
+    // The sections that contain the current position, the root first.
+    let mut open = vec![root];
+    for (level, heading, start) in headings(contents) {
+        while open.last().is_some_and(|section| section.level >= level) {
+            close(&mut open, start);
+        }
+        open.push(Section {
+            heading: Some(heading),
+            level,
+            range: start..contents.len(),
+            line: contents[..start].matches('\n').count(),
+            children: Vec::new(),
+        });
+    }
+    while open.len() > 1 {
+        close(&mut open, contents.len());
+    }
+
+    open.pop().unwrap()
+}
+
+/// Ends the innermost open section at `end` and adds it to its parent.
+fn close(open: &mut Vec<Section>, end: usize) {
+    let mut section = open.pop().unwrap();
+    section.range.end = end;
+    if let Some(parent) = open.last_mut() {
+        parent.children.push(section);
+    }
+}
+
+/// The level, text and start of every heading in `contents`.
+fn headings(contents: &str) -> Vec<(usize, String, usize)> {
+    let mut headings = Vec::new();
+    // The heading being read.
+    let mut current: Option<(usize, String, usize)> = None;
+
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    for (event, range) in Parser::new_ext(contents, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) => {
+                current = Some((level_number(level), String::new(), range.start));
+            }
+            Event::End(Tag::Heading(..)) => headings.extend(current.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading, _)) = &mut current {
+                    heading.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+fn level_number(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::parse_file_into_paragraphs;
+    use crate::include;
+    use std::{fs, path::Path};
+
+    /// A chapter of the Rust book with the listings it includes, which
+    /// leave lines starting with `#` in its code blocks.
+    fn chapter() -> String {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/book/src");
+        let contents = fs::read_to_string(src.join("ch05-02-example-structs.md")).unwrap();
+        include::expand_includes(&contents, &src).contents
+    }
+
+    fn headings(section: &Section) -> Vec<(Option<&str>, usize, usize)> {
+        section
+            .flatten()
+            .iter()
+            .map(|s| (s.heading.as_deref(), s.level, s.line))
+            .collect()
+    }
+
+    #[test]
+    fn hidden_lines_are_not_headings() {
+        let chapter = chapter();
+        assert!(chapter.contains("\n# fn main() {\n"));
+
+        let root = parse_sections(&chapter);
+        assert_eq!(
+            headings(&root),
+            [
+                (None, 0, 0),
+                (Some("An Example Program Using Structs"), 2, 0),
+                (Some("Refactoring with Tuples"), 3, 59),
+                (Some("Refactoring with Structs: Adding More Meaning"), 3, 87),
+            ]
+        );
+    }
+
+    #[test]
+    fn sections_end_at_the_next_heading_of_their_level() {
+        let chapter = chapter();
+        let root = parse_sections(&chapter);
+        let example = &root.children[0];
+        assert_eq!(example.range, 0..chapter.len());
+        assert_eq!(example.children.len(), 2);
+
+        let tuples = &example.children[0];
+        assert!(chapter[example.own_range()].ends_with("width and height together.\n\n"));
+        assert!(chapter[tuples.range.clone()].starts_with("### Refactoring with Tuples\n"));
+        assert!(chapter[tuples.range.clone()].ends_with("less obvious.\n\n"));
+
+        let structs = &example.children[1];
+        assert_eq!(structs.range.start, tuples.range.end);
+        assert_eq!(structs.range.end, chapter.len());
+    }
+
+    #[test]
+    fn paragraphs_start_with_their_heading() {
+        let chapter = chapter();
+        let paragraphs = parse_file_into_paragraphs(&chapter);
+        assert_eq!(paragraphs.len(), 3);
+
+        assert!(paragraphs[0]
+            .contents
+            .starts_with("## An Example Program Using Structs\n"));
+        assert!(paragraphs[0].contents.contains("# fn main() {"));
+        assert_eq!(
+            paragraphs[1].heading.as_deref(),
+            Some("Refactoring with Tuples")
+        );
+        assert_eq!(paragraphs[1].line, 59);
+        assert!(paragraphs[1]
+            .contents
+            .contains("dimensions.0 * dimensions.1"));
+
+        let last = paragraphs.last().unwrap();
+        assert_eq!(
+            last.heading.as_deref(),
+            Some("Refactoring with Structs: Adding More Meaning")
+        );
+        assert!(last.contents.ends_with("type `u32`.\n"));
+    }
+}
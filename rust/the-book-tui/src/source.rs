@@ -0,0 +1,240 @@
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::book::Result;
+use crate::download;
+
+const RUST_BOOK_TITLE: &str = "The Rust Programming Language";
+/// The edition kept in `~/.the-book-tui/book`, the others get their own directory.
+pub const DEFAULT_EDITION: &str = "main";
+
+/// Where a book is read from.
+pub enum BookSource {
+    /// An edition of The Rust Book, downloaded from GitHub on the first
+    /// run. Editions are branches, tags or commits of its repository.
+    RustBook(String),
+    /// An mdBook on this machine, read in place.
+    Local(PathBuf),
+    /// An mdBook in a git repository, cloned on the first run.
+    Git(String),
+}
+
+#[derive(Deserialize, Default)]
+struct BookToml {
+    #[serde(default)]
+    book: BookSection,
+}
+
+#[derive(Deserialize, Default)]
+struct BookSection {
+    title: Option<String>,
+    src: Option<PathBuf>,
+}
+
+/// What the app needs to know about a book once it is on disk.
+pub struct BookConfig {
+    pub title: String,
+    /// The directory with `SUMMARY.md` and the chapters.
+    pub src_dir: PathBuf,
+}
+
+fn app_dir() -> PathBuf {
+    match home::home_dir() {
+        Some(path) => path.join(".the-book-tui"),
+        None => {
+            panic!("Impossible to get your home dir! Please set your $HOME environment variable.")
+        }
+    }
+}
+
+impl BookSource {
+    /// A book is an existing directory or a git URL, anything else is
+    /// most likely a mistyped path and an error. Without a book,
+    /// `edition` picks which edition of The Rust Book.
+    pub fn from_arg(arg: Option<&str>, edition: Option<&str>) -> Result<Self> {
+        match arg {
+            None => Ok(BookSource::RustBook(
+                edition.unwrap_or(DEFAULT_EDITION).to_string(),
+            )),
+            Some(arg) if Path::new(arg).is_dir() => Ok(BookSource::Local(PathBuf::from(arg))),
+            Some(url) if is_git_url(url) => Ok(BookSource::Git(url.to_string())),
+            Some(arg) => Err(format!("{} is neither a directory nor a git URL", arg).into()),
+        }
+    }
+
+    /// The directory this book is kept in. Every book from
+    /// a repository gets its own, named after the URL.
+    pub fn cache_dir(&self) -> PathBuf {
+        match self {
+            BookSource::RustBook(edition) if edition == DEFAULT_EDITION => app_dir().join("book"),
+            BookSource::RustBook(edition) => {
+                app_dir().join("editions").join(url_to_dir_name(edition))
+            }
+            BookSource::Local(path) => path.clone(),
+            BookSource::Git(url) => app_dir().join("books").join(url_to_dir_name(url)),
+        }
+    }
+
+    /// Makes sure the book is on disk and reads its `book.toml`.
+    pub fn prepare(&self) -> Result<BookConfig> {
+        match self {
+            BookSource::RustBook(edition) => Ok(BookConfig {
+                title: match edition.as_str() {
+                    DEFAULT_EDITION => RUST_BOOK_TITLE.to_string(),
+                    edition => format!("{} ({})", RUST_BOOK_TITLE, edition),
+                },
+                src_dir: self.cache_dir().join("src"),
+            }),
+            BookSource::Local(path) => read_config(path),
+            BookSource::Git(url) => {
+                let dir = self.cache_dir();
+                if !dir.is_dir() {
+                    clone(url, &dir)?;
+                }
+                read_config(&dir)
+            }
+        }
+    }
+
+    /// Brings a cached book up to date with where it came from.
+    /// Local books are read in place so there is nothing to do.
+    pub fn update(&self) -> Result<()> {
+        match self {
+            BookSource::RustBook(edition) => {
+                let update = download::update_the_book(&self.cache_dir(), edition)?;
+                match update.downloaded + update.removed {
+                    0 => println!("The book is up to date at {}", update.commit),
+                    _ => println!(
+                        "Updated the book to {}: {} files downloaded, {} removed",
+                        update.commit, update.downloaded, update.removed
+                    ),
+                }
+                Ok(())
+            }
+            BookSource::Local(path) => Err(format!(
+                "{} is read in place, there is nothing to update",
+                path.display()
+            )
+            .into()),
+            BookSource::Git(url) => {
+                let dir = self.cache_dir();
+                if !dir.is_dir() {
+                    return clone(url, &dir);
+                }
+                let status = Command::new("git")
+                    .arg("-C")
+                    .arg(&dir)
+                    .args(["pull", "--ff-only"])
+                    .status()?;
+                if !status.success() {
+                    return Err(format!("Could not update {}", url).into());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// URLs like `https://github.com/rust-lang/book` or
+/// `git@github.com:rust-lang/book.git`.
+fn is_git_url(arg: &str) -> bool {
+    if arg.contains("://") {
+        return true;
+    }
+    // scp-like syntax, `user@host:path`.
+    match arg.split_once(':') {
+        Some((host, _)) => host.contains('@') && !host.contains('/'),
+        None => false,
+    }
+}
+
+/// A readable name with a hash of the URL, so that URLs whose
+/// readable names are the same, like `github.com/a/b-c` and
+/// `github.com/a-b/c`, don't share a directory.
+fn url_to_dir_name(url: &str) -> String {
+    let url = url.trim_end_matches('/').trim_end_matches(".git");
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name = url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    let hash = sha1_smol::Sha1::from(url).digest().to_string();
+    format!("{}-{}", name, &hash[..8])
+}
+
+fn clone(url: &str, dir: &Path) -> Result<()> {
+    eprintln!("Book not found. Cloning {}...", url);
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let status = Command::new("git")
+        // `--` so a URL starting with `-` isn't read as an option.
+        .args(["clone", "--depth", "1", "--", url])
+        .arg(dir)
+        .status()?;
+
+    if !status.success() {
+        // A failed clone can leave a partial directory behind.
+        let _ = fs::remove_dir_all(dir);
+        return Err(format!("Could not clone {}", url).into());
+    }
+    Ok(())
+}
+
+/// Reads `book.toml` in `dir` if there is one, mdBook's defaults otherwise.
+fn read_config(dir: &Path) -> Result<BookConfig> {
+    let book_toml = dir.join("book.toml");
+    let config = match fs::read_to_string(&book_toml) {
+        Ok(contents) => toml::from_str::<BookToml>(&contents)
+            .map_err(|err| format!("Could not read {}: {}", book_toml.display(), err))?,
+        Err(_) => BookToml::default(),
+    };
+
+    let src_dir = dir.join(config.book.src.unwrap_or_else(|| PathBuf::from("src")));
+    if !src_dir.join("SUMMARY.md").is_file() {
+        return Err(format!("{} is not an mdBook, it has no SUMMARY.md", dir.display()).into());
+    }
+
+    let title = match config.book.title {
+        Some(title) => title,
+        None => dir
+            .canonicalize()?
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    Ok(BookConfig { title, src_dir })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similar_urls_get_their_own_directory() {
+        assert_ne!(
+            url_to_dir_name("https://github.com/a/b-c"),
+            url_to_dir_name("https://github.com/a-b/c")
+        );
+        assert_eq!(
+            url_to_dir_name("https://github.com/a/b.git"),
+            url_to_dir_name("https://github.com/a/b/")
+        );
+        assert!(url_to_dir_name("https://github.com/a/b").starts_with("github-com-a-b-"));
+    }
+
+    #[test]
+    fn only_urls_are_cloned() {
+        assert!(is_git_url("https://github.com/rust-lang/book"));
+        assert!(is_git_url("git@github.com:rust-lang/book.git"));
+        assert!(!is_git_url("~/code/my-book"));
+        assert!(!is_git_url("books/my@book:draft/src"));
+        assert!(BookSource::from_arg(Some("no/such/book"), None).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+
+/// A chapter listed in `SUMMARY.md`.
+#[derive(Clone, Debug)]
+pub struct SummaryItem {
+    pub title: String,
+    /// Relative to the book's src directory. None for draft
+    /// chapters, which are listed with an empty link.
+    pub path: Option<PathBuf>,
+    /// How deeply the chapter is nested, 0 for top level ones.
+    pub level: usize,
+    /// Like "3.1." for numbered chapters. Prefix and suffix
+    /// chapters outside of the list have no number.
+    pub number: Option<String>,
+}
+
+/// Reads the chapters from the contents of an mdBook `SUMMARY.md`
+/// in the order they are listed. Part titles and separators
+/// are skipped since they don't have a page.
+pub fn parse_summary(contents: &str) -> Vec<SummaryItem> {
+    let mut items = Vec::new();
+    // Indentation of the list items that contain the current one.
+    let mut indents: Vec<usize> = Vec::new();
+    // Chapter numbers at each level, the last one is the current chapter's.
+    let mut numbers: Vec<usize> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let list_item = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "));
+
+        let (title, path) = match parse_link(list_item.unwrap_or(trimmed)) {
+            Some(link) => link,
+            None => continue,
+        };
+
+        if list_item.is_none() {
+            items.push(SummaryItem {
+                title,
+                path,
+                level: 0,
+                number: None,
+            });
+            continue;
+        }
+
+        let indent = indentation(line);
+        while indents.last().is_some_and(|last| *last >= indent) {
+            indents.pop();
+        }
+        let level = indents.len();
+        indents.push(indent);
+
+        numbers.truncate(level + 1);
+        while numbers.len() < level + 1 {
+            numbers.push(0);
+        }
+        numbers[level] += 1;
+        let number = numbers
+            .iter()
+            .map(|n| format!("{}.", n))
+            .collect::<String>();
+
+        items.push(SummaryItem {
+            title,
+            path,
+            level,
+            number: Some(number),
+        });
+    }
+
+    items
+}
+
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Parses `[title](path)`. An empty path marks a draft chapter.
+fn parse_link(text: &str) -> Option<(String, Option<PathBuf>)> {
+    let text = text.trim().strip_prefix('[')?;
+    let (title, rest) = text.rsplit_once("](")?;
+    let path = rest.strip_suffix(')')?.trim();
+
+    let path = match path {
+        "" => None,
+        path => Some(PathBuf::from(path.trim_start_matches("./"))),
+    };
+    Some((title.to_string(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    /// The start and some parts of the Rust book's `SUMMARY.md`.
+    fn rust_book() -> Vec<SummaryItem> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/book/src/SUMMARY.md");
+        parse_summary(&fs::read_to_string(path).unwrap())
+    }
+
+    fn item(
+        title: &str,
+        path: &str,
+        level: usize,
+        number: Option<&str>,
+    ) -> (String, Option<PathBuf>, usize, Option<String>) {
+        (
+            title.to_string(),
+            Some(PathBuf::from(path)),
+            level,
+            number.map(str::to_string),
+        )
+    }
+
+    fn fields(items: &[SummaryItem]) -> Vec<(String, Option<PathBuf>, usize, Option<String>)> {
+        items
+            .iter()
+            .map(|item| {
+                (
+                    item.title.clone(),
+                    item.path.clone(),
+                    item.level,
+                    item.number.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prefix_chapters_have_no_number() {
+        let items = rust_book();
+        assert_eq!(
+            fields(&items[..4]),
+            [
+                item("The Rust Programming Language", "title-page.md", 0, None),
+                item("Foreword", "foreword.md", 0, None),
+                item("Introduction", "ch00-00-introduction.md", 0, None),
+                item(
+                    "Getting Started",
+                    "ch01-00-getting-started.md",
+                    0,
+                    Some("1.")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_chapters_are_numbered_by_their_place_in_the_list() {
+        let items = rust_book();
+        assert_eq!(items.len(), 21);
+        assert_eq!(
+            fields(&items[4..7]),
+            [
+                item("Installation", "ch01-01-installation.md", 1, Some("1.1.")),
+                item("Hello, World!", "ch01-02-hello-world.md", 1, Some("1.2.")),
+                item("Hello, Cargo!", "ch01-03-hello-cargo.md", 1, Some("1.3.")),
+            ]
+        );
+        assert_eq!(
+            fields(&items[7..9]),
+            [
+                item(
+                    "Programming a Guessing Game",
+                    "ch02-00-guessing-game-tutorial.md",
+                    0,
+                    Some("2.")
+                ),
+                item(
+                    "Common Programming Concepts",
+                    "ch03-00-common-programming-concepts.md",
+                    0,
+                    Some("3.")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn part_titles_do_not_restart_the_numbers() {
+        let items = rust_book();
+        let example = items
+            .iter()
+            .find(|item| item.title == "An Example Program Using Structs")
+            .unwrap();
+        assert_eq!(example.number.as_deref(), Some("4.2."));
+        assert_eq!(example.level, 1);
+
+        let last = items.last().unwrap();
+        assert_eq!(last.title, "B - Operators and Symbols");
+        assert_eq!(last.number.as_deref(), Some("5.2."));
+        assert!(items
+            .iter()
+            .all(|item| !item.title.starts_with("Getting started")));
+    }
+
+    #[test]
+    fn draft_chapters_have_no_path() {
+        let items = parse_summary("- [Intro](./intro.md)\n- [Someday]()\n    * [Later]( )\n");
+        assert_eq!(
+            fields(&items),
+            [
+                item("Intro", "intro.md", 0, Some("1.")),
+                (String::from("Someday"), None, 0, Some(String::from("2."))),
+                (String::from("Later"), None, 1, Some(String::from("2.1."))),
+            ]
+        );
+    }
+
+    #[test]
+    fn links_in_code_blocks_are_not_chapters() {
+        let items = parse_summary("```md\n- [Example](example.md)\n```\n- [Real](real.md)\n");
+        assert_eq!(fields(&items), [item("Real", "real.md", 0, Some("1."))]);
+    }
+}
@@ -0,0 +1,17 @@
+// ANCHOR: all
+fn main() {
+    let width1 = 30;
+    let height1 = 50;
+
+    println!(
+        "The area of the rectangle is {} square pixels.",
+        area(width1, height1)
+    );
+}
+
+// ANCHOR: here
+fn area(width: u32, height: u32) -> u32 {
+    width * height
+}
+// ANCHOR_END: here
+// ANCHOR_END: all
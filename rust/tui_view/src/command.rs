@@ -0,0 +1,158 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A command that can be run from the command palette.
+#[derive(Debug, Clone)]
+pub struct Command {
+    /// Passed to `Opts::run_command` when the command is chosen.
+    pub name: String,
+    /// What the command does.
+    pub description: String,
+    /// The keys the command is also bound to, e.g. `<C-q>`.
+    pub keys: Option<String>,
+    /// The prompt shown when the command needs an argument
+    /// typed in the palette before it runs.
+    pub argument: Option<String>,
+}
+
+impl Command {
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            keys: None,
+            argument: None,
+        }
+    }
+
+    pub fn with_keys(mut self, keys: &str) -> Self {
+        self.keys = Some(keys.to_string());
+        self
+    }
+
+    pub fn with_argument(mut self, prompt: &str) -> Self {
+        self.argument = Some(prompt.to_string());
+        self
+    }
+}
+
+/// The commands handled by the library itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Exit,
+    ScrollDown,
+    ScrollUp,
+    Next,
+    Previous,
+    ToggleDock,
+    TogglePopup,
+    Suspend,
+    ToggleMark,
+    ToggleVisual,
+    ToggleMarkAll,
+    Help,
+    Palette,
+    Search,
+    ToggleRelevancy,
+    NextLink,
+    PreviousLink,
+    FollowLink,
+    Back,
+    Forward,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 20] = [
+        Builtin::Exit,
+        Builtin::ScrollDown,
+        Builtin::ScrollUp,
+        Builtin::Next,
+        Builtin::Previous,
+        Builtin::ToggleDock,
+        Builtin::TogglePopup,
+        Builtin::Suspend,
+        Builtin::ToggleMark,
+        Builtin::ToggleVisual,
+        Builtin::ToggleMarkAll,
+        Builtin::Help,
+        Builtin::Palette,
+        Builtin::Search,
+        Builtin::ToggleRelevancy,
+        Builtin::NextLink,
+        Builtin::PreviousLink,
+        Builtin::FollowLink,
+        Builtin::Back,
+        Builtin::Forward,
+    ];
+
+    /// `?` and `:` only count when the search is
+    /// empty so they can still be searched for.
+    pub fn from_key(key: &KeyEvent, search_is_empty: bool) -> Option<Self> {
+        let builtin = match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('e')) => Builtin::Exit,
+            (KeyModifiers::CONTROL, KeyCode::Char('d')) => Builtin::ScrollDown,
+            (KeyModifiers::CONTROL, KeyCode::Char('u')) => Builtin::ScrollUp,
+            (KeyModifiers::CONTROL, KeyCode::Char('j')) => Builtin::Next,
+            (KeyModifiers::CONTROL, KeyCode::Char('k')) => Builtin::Previous,
+            (KeyModifiers::CONTROL, KeyCode::Char('b')) => Builtin::ToggleDock,
+            (KeyModifiers::CONTROL, KeyCode::Char('p')) => Builtin::TogglePopup,
+            (KeyModifiers::CONTROL, KeyCode::Char('z')) => Builtin::Suspend,
+            (KeyModifiers::CONTROL, KeyCode::Char(' ')) => Builtin::ToggleMark,
+            (KeyModifiers::CONTROL, KeyCode::Char('v')) => Builtin::ToggleVisual,
+            (KeyModifiers::CONTROL, KeyCode::Char('a')) => Builtin::ToggleMarkAll,
+            (KeyModifiers::CONTROL, KeyCode::Char('o')) => Builtin::Palette,
+            (KeyModifiers::CONTROL, KeyCode::Char('l')) => Builtin::FollowLink,
+            (KeyModifiers::NONE, KeyCode::Tab) => Builtin::NextLink,
+            (_, KeyCode::BackTab) => Builtin::PreviousLink,
+            (KeyModifiers::ALT, KeyCode::Left) => Builtin::Back,
+            (KeyModifiers::ALT, KeyCode::Right) => Builtin::Forward,
+            (_, KeyCode::F(1)) => Builtin::Help,
+            (_, KeyCode::F(2)) => Builtin::ToggleRelevancy,
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char('?')) if search_is_empty => {
+                Builtin::Help
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(':')) if search_is_empty => {
+                Builtin::Palette
+            }
+            _ => return None,
+        };
+        Some(builtin)
+    }
+
+    pub fn command(self) -> Command {
+        let (name, description, keys) = match self {
+            Builtin::Exit => ("exit", "Exit", "<C-e>"),
+            Builtin::ScrollDown => ("scroll-down", "Scroll content down", "<C-d>"),
+            Builtin::ScrollUp => ("scroll-up", "Scroll content up", "<C-u>"),
+            Builtin::Next => ("next", "Select next dock item", "<C-j>"),
+            Builtin::Previous => ("previous", "Select previous dock item", "<C-k>"),
+            Builtin::ToggleDock => ("toggle-dock", "Toggle dock", "<C-b>"),
+            Builtin::TogglePopup => ("toggle-popup", "Toggle popup", "<C-p>"),
+            Builtin::Suspend => ("suspend", "Suspend to the shell", "<C-z>"),
+            Builtin::ToggleMark => ("mark", "Mark selected dock item", "<C-Space>"),
+            Builtin::ToggleVisual => ("range-select", "Start or finish a range selection", "<C-v>"),
+            Builtin::ToggleMarkAll => ("mark-all", "Mark all items matching the search", "<C-a>"),
+            Builtin::Help => ("help", "Toggle the keybind help", "<F1>, ?"),
+            Builtin::Palette => ("palette", "Open the command palette", "<C-o>, :"),
+            Builtin::ToggleRelevancy => {
+                ("relevancy", "Show how the search ranked the pages", "<F2>")
+            }
+            Builtin::NextLink => ("next-link", "Select the next link in the reader", "<Tab>"),
+            Builtin::PreviousLink => (
+                "previous-link",
+                "Select the previous link in the reader",
+                "<S-Tab>",
+            ),
+            Builtin::FollowLink => (
+                "follow-link",
+                "Go to the page of the selected link",
+                "<C-l>",
+            ),
+            Builtin::Back => ("back", "Go back to the page before", "<A-Left>"),
+            Builtin::Forward => ("forward", "Go forward again after going back", "<A-Right>"),
+            Builtin::Search => {
+                return Command::new("search", "Replace the search").with_argument("Search")
+            }
+        };
+        Command::new(name, description).with_keys(keys)
+    }
+}
@@ -0,0 +1,48 @@
+use std::{fmt, io};
+
+/// What the crate's own functions return.
+pub(crate) type Result<T> = std::result::Result<T, Error>;
+
+/// Errors `create_view` and the other entry points can return.
+#[derive(Debug)]
+pub enum Error {
+    /// Taking over or giving back the terminal failed.
+    Terminal(io::Error),
+    /// Drawing a frame or reading an event failed while the app was running.
+    Io(io::Error),
+    /// The app was closed by a terminating signal like SIGTERM.
+    /// Holds the signal number.
+    Interrupted(i32),
+    /// Writing or reading a recorded session failed.
+    Recording(io::Error),
+    /// Fetching pages from a `PageSource` failed where
+    /// it can't be shown in a popup. Holds the message.
+    Source(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Terminal(err) => write!(f, "could not set up the terminal: {}", err),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Interrupted(signal) => write!(f, "interrupted by signal {}", signal),
+            Error::Recording(err) => write!(f, "recording failed: {}", err),
+            Error::Source(message) => write!(f, "could not load pages: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Terminal(err) | Error::Io(err) | Error::Recording(err) => Some(err),
+            Error::Interrupted(_) | Error::Source(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,99 @@
+use crossterm::event::{self, Event};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Set to a file path to record every input event of a
+/// session into it, e.g. to attach it to a bug report.
+pub const RECORD_ENV_VAR: &str = "TUI_VIEW_RECORD";
+
+/// Where the event loop gets its input from.
+pub trait EventSource {
+    /// Waits up to `timeout` for an event to be available.
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool>;
+    fn read(&mut self) -> io::Result<Event>;
+    /// Whether there will be no more events. The event
+    /// loop exits once this returns true.
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Input from the real terminal.
+pub struct TerminalEvents;
+
+impl EventSource for TerminalEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        event::read()
+    }
+}
+
+/// The first line of a recording.
+#[derive(Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Every following line of a recording.
+#[derive(Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the recording started.
+    pub ms: u64,
+    pub event: Event,
+}
+
+/// Passes events through from another source and writes
+/// each of them to a file as a line of JSON.
+pub struct Recorder<S: EventSource> {
+    source: S,
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl<S: EventSource> Recorder<S> {
+    pub fn create(source: S, path: &Path, width: u16, height: u16) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut file, &RecordingHeader { width, height })?;
+        writeln!(file)?;
+
+        Ok(Self {
+            source,
+            file,
+            started: Instant::now(),
+        })
+    }
+}
+
+impl<S: EventSource> EventSource for Recorder<S> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.source.poll(timeout)
+    }
+
+    fn read(&mut self) -> io::Result<Event> {
+        let event = self.source.read()?;
+        let recorded = RecordedEvent {
+            ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+
+        serde_json::to_writer(&mut self.file, &recorded)?;
+        writeln!(self.file)?;
+        // Flushed every time so nothing is lost if the app crashes.
+        self.file.flush()?;
+
+        Ok(recorded.event)
+    }
+
+    fn finished(&self) -> bool {
+        self.source.finished()
+    }
+}
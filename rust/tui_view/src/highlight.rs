@@ -0,0 +1,265 @@
+use ratatui::text::Text;
+
+#[cfg(feature = "highlight")]
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+#[cfg(feature = "highlight")]
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+#[cfg(feature = "highlight")]
+const THEME: &str = "base16-ocean.dark";
+
+/// Turns what the reader shows into styled text, highlighting code
+/// in fenced blocks or whole pages that have a language. Anything
+/// it doesn't know the language of is left as plain text.
+#[derive(Default)]
+pub struct Highlighter {
+    /// The grammars and theme, loaded on first use.
+    #[cfg(feature = "highlight")]
+    engine: Option<(SyntaxSet, Theme)>,
+    /// The reader is drawn far more often than its
+    /// contents change, so the last result is kept.
+    last: Option<(String, Option<String>, Text<'static>)>,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn highlight(&mut self, contents: &str, language: Option<&str>) -> Text<'static> {
+        if let Some((last_contents, last_language, text)) = &self.last {
+            if last_contents == contents && last_language.as_deref() == language {
+                return text.clone();
+            }
+        }
+
+        let text = self.render(contents, language);
+        self.last = Some((
+            contents.to_string(),
+            language.map(str::to_string),
+            text.clone(),
+        ));
+        text
+    }
+
+    #[cfg(not(feature = "highlight"))]
+    fn render(&mut self, contents: &str, _language: Option<&str>) -> Text<'static> {
+        Text::from(contents.to_string())
+    }
+
+    #[cfg(feature = "highlight")]
+    fn render(&mut self, contents: &str, language: Option<&str>) -> Text<'static> {
+        let (syntaxes, theme) = &*self.engine.get_or_insert_with(|| {
+            let themes = ThemeSet::load_defaults();
+            (
+                SyntaxSet::load_defaults_newlines(),
+                themes.themes[THEME].clone(),
+            )
+        });
+
+        if let Some(syntax) = language.and_then(|l| find_syntax(syntaxes, l)) {
+            let mut lines = HighlightLines::new(syntax, theme);
+            let spans = contents
+                .lines()
+                .map(|line| highlight_line(&mut lines, syntaxes, line))
+                .collect::<Vec<Spans>>();
+            return Text::from(spans);
+        }
+
+        let mut spans = Vec::new();
+        // The fence that closes the block and how to highlight it.
+        let mut block: Option<(&str, Option<HighlightLines>)> = None;
+        for line in contents.lines() {
+            match &mut block {
+                Some((fence, _)) if is_fence_end(line, fence) => {
+                    block = None;
+                    spans.push(Spans::from(line.to_string()));
+                }
+                Some((_, Some(lines))) => spans.push(highlight_line(lines, syntaxes, line)),
+                Some((_, None)) => spans.push(Spans::from(line.to_string())),
+                None => {
+                    if let Some((fence, info)) = fence_start(line) {
+                        let syntax = block_language(info).and_then(|l| find_syntax(syntaxes, l));
+                        block = Some((fence, syntax.map(|s| HighlightLines::new(s, theme))));
+                    }
+                    spans.push(Spans::from(line.to_string()));
+                }
+            }
+        }
+        Text::from(spans)
+    }
+}
+
+#[cfg(feature = "highlight")]
+fn find_syntax<'a>(syntaxes: &'a SyntaxSet, language: &str) -> Option<&'a SyntaxReference> {
+    syntaxes
+        .find_syntax_by_token(language)
+        .or_else(|| syntaxes.find_syntax_by_token(&language.to_lowercase()))
+}
+
+/// Returns the fence and the info string after it
+/// if `line` opens a fenced code block.
+#[cfg(feature = "highlight")]
+fn fence_start(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let marker = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = line.chars().take_while(|c| *c == marker).count();
+    if length < 3 {
+        return None;
+    }
+    Some(line.split_at(length))
+}
+
+/// A block is closed by a fence of the same character
+/// that is at least as long as the one that opened it.
+#[cfg(feature = "highlight")]
+fn is_fence_end(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    line.starts_with(fence) && line.trim_start_matches(&fence[..1]).is_empty()
+}
+
+/// The language of a block is the first word of its info
+/// string, e.g. `rust` in mdBook's `rust,ignore`.
+#[cfg(feature = "highlight")]
+fn block_language(info: &str) -> Option<&str> {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .find(|word| !word.is_empty())
+}
+
+#[cfg(feature = "highlight")]
+fn highlight_line(lines: &mut HighlightLines, syntaxes: &SyntaxSet, line: &str) -> Spans<'static> {
+    // The bundled grammars expect lines to end with a newline.
+    let line = format!("{}\n", line);
+    let ranges = match lines.highlight_line(&line, syntaxes) {
+        Ok(ranges) => ranges,
+        Err(_) => return Spans::from(line.trim_end_matches('\n').to_string()),
+    };
+
+    let spans = ranges
+        .into_iter()
+        .map(|(style, text)| Span::styled(text.trim_end_matches('\n').to_string(), to_style(style)))
+        .filter(|span| !span.content.is_empty())
+        .collect::<Vec<Span>>();
+    Spans::from(spans)
+}
+
+/// Only the foreground is taken from the theme so
+/// code blends in with the rest of the reader.
+#[cfg(feature = "highlight")]
+fn to_style(style: syntect::highlighting::Style) -> Style {
+    let color = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(color.r, color.g, color.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "fn main() {\n    println!(\"hi\");\n}";
+
+    fn is_plain(text: &Text) -> bool {
+        text.lines
+            .iter()
+            .flat_map(|spans| &spans.0)
+            .all(|span| span.style == Default::default())
+    }
+
+    #[test]
+    fn unknown_languages_are_plain_text() {
+        let mut highlighter = Highlighter::new();
+        let text = highlighter.highlight(CODE, Some("no-such-language"));
+        assert_eq!(text, Text::from(CODE));
+        assert!(is_plain(&text));
+
+        let contents = format!("Before\n```no-such-language\n{}\n```\nAfter", CODE);
+        let text = highlighter.highlight(&contents, None);
+        assert_eq!(text, Text::from(contents.as_str()));
+    }
+
+    #[test]
+    fn pages_without_a_language_or_fences_are_plain_text() {
+        let mut highlighter = Highlighter::new();
+        let text = highlighter.highlight(CODE, None);
+        assert_eq!(text, Text::from(CODE));
+    }
+
+    #[cfg(not(feature = "highlight"))]
+    #[test]
+    fn known_languages_are_plain_text_without_the_feature() {
+        let mut highlighter = Highlighter::new();
+        let text = highlighter.highlight(CODE, Some("rust"));
+        assert_eq!(text, Text::from(CODE));
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn known_languages_are_highlighted() {
+        let mut highlighter = Highlighter::new();
+        let text = highlighter.highlight(CODE, Some("rust"));
+        assert_eq!(text.lines.len(), 3);
+        assert!(!is_plain(&text));
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn only_code_in_fenced_blocks_is_highlighted() {
+        let contents = format!("Before\n```rust,ignore\n{}\n```\nAfter", CODE);
+        let text = Highlighter::new().highlight(&contents, None);
+        let lines = &text.lines;
+
+        assert_eq!(lines.len(), 7);
+        assert!(is_plain(&Text::from(lines[..2].to_vec())));
+        assert!(!is_plain(&Text::from(lines[2..5].to_vec())));
+        assert!(is_plain(&Text::from(lines[5..].to_vec())));
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn fences_open_with_three_backticks_or_tildes() {
+        assert_eq!(fence_start("```rust"), Some(("```", "rust")));
+        assert_eq!(
+            fence_start("  ~~~~ rust,ignore"),
+            Some(("~~~~", " rust,ignore"))
+        );
+        assert_eq!(fence_start("```"), Some(("```", "")));
+        assert_eq!(fence_start("``rust"), None);
+        assert_eq!(fence_start("let x = 1;"), None);
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn fences_close_with_at_least_as_many_of_the_same_character() {
+        assert!(is_fence_end("```", "```"));
+        assert!(is_fence_end("  `````  ", "```"));
+        assert!(!is_fence_end("``", "```"));
+        assert!(!is_fence_end("~~~", "```"));
+        assert!(!is_fence_end("```rust", "```"));
+    }
+
+    #[cfg(feature = "highlight")]
+    #[test]
+    fn the_language_is_the_first_word_of_the_info_string() {
+        assert_eq!(block_language("rust"), Some("rust"));
+        assert_eq!(block_language(" rust,ignore"), Some("rust"));
+        assert_eq!(block_language("console title=\"x\""), Some("console"));
+        assert_eq!(block_language(""), None);
+        assert_eq!(block_language(" , "), None);
+    }
+}
@@ -0,0 +1,159 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::command::Builtin;
+
+/// Describes a keybind so it can be listed in the help overlay.
+#[derive(Debug, Clone)]
+pub struct Keybind {
+    /// How the keys are displayed, e.g. `<C-q>`.
+    pub keys: String,
+    /// What the keybind does.
+    pub description: String,
+}
+
+impl Keybind {
+    pub fn new(keys: &str, description: &str) -> Self {
+        Self {
+            keys: keys.to_string(),
+            description: description.to_string(),
+        }
+    }
+}
+
+/// The keybinds handled by the library itself.
+pub fn builtin() -> Vec<Keybind> {
+    let mut keybinds = Builtin::ALL
+        .iter()
+        .map(|builtin| builtin.command())
+        .filter_map(|command| Some(Keybind::new(&command.keys?, &command.description)))
+        .collect::<Vec<Keybind>>();
+
+    keybinds.push(Keybind::new("<Esc>", "Cancel range selection"));
+    keybinds
+}
+
+/// Parses keys written the way they are displayed, like `<C-q>`,
+/// `<Enter>` or `a`. The angle brackets are optional.
+pub fn parse_keys(text: &str) -> Option<KeyEvent> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('<')
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or(text);
+
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text;
+    while let Some((prefix, key)) = rest.split_once('-') {
+        // `C--` is Ctrl and the minus key.
+        if key.is_empty() {
+            break;
+        }
+        modifiers |= match prefix {
+            "C" | "c" => KeyModifiers::CONTROL,
+            "A" | "a" | "M" | "m" => KeyModifiers::ALT,
+            "S" | "s" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        rest = key;
+    }
+
+    let code = match rest.to_lowercase().as_str() {
+        "enter" | "cr" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        lower => match lower.strip_prefix('f').map(str::parse::<u8>) {
+            Some(Ok(n)) => KeyCode::F(n),
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        },
+    };
+
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Option<KeyEvent> {
+        Some(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn parses_keys_with_modifiers() {
+        assert_eq!(
+            parse_keys("<C-q>"),
+            key(KeyCode::Char('q'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse_keys("C-q"),
+            key(KeyCode::Char('q'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse_keys("<C-A-x>"),
+            key(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )
+        );
+        assert_eq!(
+            parse_keys("<m-Enter>"),
+            key(KeyCode::Enter, KeyModifiers::ALT)
+        );
+        assert_eq!(
+            parse_keys("<C-->"),
+            key(KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+    }
+
+    #[test]
+    fn parses_named_keys() {
+        assert_eq!(
+            parse_keys("<Enter>"),
+            key(KeyCode::Enter, KeyModifiers::NONE)
+        );
+        assert_eq!(parse_keys("<CR>"), key(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(parse_keys("<esc>"), key(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(
+            parse_keys("<BS>"),
+            key(KeyCode::Backspace, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse_keys("<Space>"),
+            key(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+        assert_eq!(parse_keys("<F12>"), key(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(
+            parse_keys(" a "),
+            key(KeyCode::Char('a'), KeyModifiers::NONE)
+        );
+        assert_eq!(parse_keys("F"), key(KeyCode::Char('F'), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert_eq!(parse_keys("<Nope>"), None);
+        assert_eq!(parse_keys("<X-q>"), None);
+        assert_eq!(parse_keys(""), None);
+    }
+
+    #[test]
+    fn parses_the_keys_of_builtin_commands() {
+        for keybind in builtin() {
+            for keys in keybind.keys.split(", ") {
+                assert!(parse_keys(keys).is_some(), "{}", keys);
+            }
+        }
+    }
+}
@@ -1,39 +1,56 @@
+mod command;
+mod error;
+mod events;
+mod highlight;
+mod keybind;
+mod palette;
+mod plain;
+mod preview;
+mod query;
+mod search;
+mod source;
+mod terminal;
+pub mod testing;
+mod text;
 mod view;
-use crossterm::{
-    cursor,
-    event::{DisableMouseCapture, EnableMouseCapture},
-    execute,
-    terminal::{
-        self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-    },
-};
+use events::{EventSource, Recorder, TerminalEvents, RECORD_ENV_VAR};
 use itertools::Itertools;
 use ratatui::{backend::CrosstermBackend, Terminal};
+use terminal::{Signals, TerminalGuard};
 use view::run_app;
 
+pub use command::Command;
 pub use crossterm::event;
+pub use error::Error;
+pub use keybind::Keybind;
+pub use plain::run_plain;
+pub use preview::PreviewJob;
+pub use query::{run_query, Query};
+pub use search::{SearchConfig, SortOrder};
+pub use source::{PageSource, SourceResult};
 pub use view::App;
 
-use std::{io, panic, rc::Rc};
-fn cleanup_terminal() {
-    let mut stdout = io::stdout();
-
-    execute!(stdout, cursor::MoveTo(0, 0)).unwrap();
-    execute!(stdout, terminal::Clear(terminal::ClearType::All)).unwrap();
-
-    execute!(stdout, terminal::LeaveAlternateScreen).unwrap();
-    execute!(stdout, cursor::Show).unwrap();
-
-    terminal::disable_raw_mode().unwrap();
-}
+use plain::PLAIN_ENV_VAR;
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    hash::{Hash, Hasher},
+    io::{self, IsTerminal},
+    panic,
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 fn setup_panic_hook() {
     panic::set_hook(Box::new(|panic_info| {
-        cleanup_terminal();
+        let _ = terminal::restore();
         better_panic::Settings::auto().create_panic_handler()(panic_info);
     }));
 }
 
+/// Any error boxed, for the code of apps built on tui_view.
+/// The entry points like `create_view` return `Error` instead.
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 /// create_view function expects a Struct that implements this trait.
@@ -42,47 +59,104 @@ pub trait Opts {
     /// This is called on (pretty much) every key event and how the
     /// consumer customizes the app's behaviour. They can match on
     /// the key event and define behaviours accordindly.
-    fn keybinds(&self, key: event::KeyEvent, app: App) -> App {
+    fn keybinds(&self, _key: event::KeyEvent, app: App) -> App {
         app
     }
     /// This is supposed to return the actual data to be
     /// loaded into the app as Page structs.
+    /// Not called if `page_source` returns a source.
     fn get_pages(&self) -> Vec<Page>;
+    /// Return a source here to load pages in batches as
+    /// the user scrolls instead of using `get_pages`.
+    fn page_source(&self) -> Option<Rc<dyn PageSource>> {
+        None
+    }
+    /// Describes the keybinds handled in `keybinds` so they
+    /// are listed in the help overlay next to the built-in ones.
+    fn describe_keybinds(&self) -> Vec<Keybind> {
+        vec![]
+    }
+    /// Commands listed in the command palette next to the built-in ones.
+    fn commands(&self) -> Vec<Command> {
+        vec![]
+    }
+    /// Called when one of the commands from `commands` is chosen in the
+    /// palette. `argument` is what was typed for commands that take one.
+    fn run_command(&self, _name: &str, _argument: Option<String>, app: App) -> App {
+        app
+    }
+    /// Return a job here to show something other than the contents of
+    /// the selected page in the reader. The job runs on a background
+    /// thread once the selection settles and its result is cached by
+    /// page title. Return None to show the contents.
+    fn preview(&self, _page: &Page) -> Option<PreviewJob> {
+        None
+    }
     /// You can define words here that will take priority in search.
     fn get_keywords(&self) -> Vec<&'static str> {
         vec![]
     }
+    /// The weights the search scores pages with and
+    /// how the pages that matched are sorted.
+    fn search_config(&self) -> SearchConfig {
+        SearchConfig::default()
+    }
 }
 
-pub fn create_view(opts: Rc<dyn Opts>) -> Result<()> {
-    better_panic::install();
+/// Takes over the terminal and runs the app until the user exits.
+/// The terminal is restored before this returns, whether the app
+/// exited normally, failed, panicked or was stopped by a signal.
+///
+/// If the `TUI_VIEW_RECORD` environment variable is set to a path, all
+/// input is recorded into that file so the session can be replayed
+/// with `testing::replay`.
+///
+/// Falls back to `create_plain_view` if stdout isn't a terminal or
+/// the `TUI_VIEW_PLAIN` environment variable is set.
+///
+/// Apps that want a non-interactive search too can call `run_query`
+/// instead, e.g. when `Query::from_args` finds a `--query`.
+pub fn create_view(opts: Rc<dyn Opts>) -> std::result::Result<(), Error> {
+    if env::var_os(PLAIN_ENV_VAR).is_some() || !io::stdout().is_terminal() {
+        return create_plain_view(opts);
+    }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
+    better_panic::install();
 
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    let signals = Signals::register().map_err(Error::Terminal)?;
+    let guard = TerminalGuard::new().map_err(Error::Terminal)?;
 
-    let mut terminal = Terminal::new(backend)?;
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend).map_err(Error::Terminal)?;
 
     setup_panic_hook();
 
+    let mut events: Box<dyn EventSource> = match env::var_os(RECORD_ENV_VAR) {
+        Some(path) => {
+            let size = terminal.size().map_err(Error::Terminal)?;
+            let recorder = Recorder::create(
+                TerminalEvents,
+                &PathBuf::from(path),
+                size.width,
+                size.height,
+            );
+            Box::new(recorder.map_err(Error::Recording)?)
+        }
+        None => Box::new(TerminalEvents),
+    };
+
     let app = App::new(opts);
-    let res = run_app(&mut terminal, app);
+    let res = run_app(&mut terminal, app, Some(&signals), events.as_mut());
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    let restored = guard.restore().map_err(Error::Terminal);
 
-    if let Err(err) = res {
-        println!("{:?}", err)
-    }
+    res.and(restored)
+}
 
-    Ok(())
+/// A line based version of the view that works with screen readers.
+/// Reads commands from stdin and writes plain text to stdout.
+pub fn create_plain_view(opts: Rc<dyn Opts>) -> std::result::Result<(), Error> {
+    run_plain(opts, io::stdin().lock(), io::stdout().lock())
 }
 
 #[derive(Debug, Clone)]
@@ -94,9 +168,51 @@ pub struct Page {
     /// A value that will be used to sort filtered
     /// pages in descending order. If None, search
     /// will try to calculate the relevancy of pages
-    /// and sort accordingly.
+    /// and sort accordingly. See `SortOrder` for how
+    /// the two are combined.
     pub sort_field: Option<f64>,
+    /// References to other pages the user can
+    /// cycle through and follow in the reader.
+    pub links: Vec<Link>,
+    /// The language the contents are written in, e.g. `rust` or
+    /// `bash`. The whole page is highlighted as code then, otherwise
+    /// only fenced code blocks in it are.
+    pub language: Option<String>,
     relevancy: u64,
+    contents_loaded: bool,
+    marked: bool,
+    /// Kept by `search` so later searches and every frame of the
+    /// relevancy breakdown don't normalize the contents again.
+    normalized: Option<Normalized>,
+    /// Tells pages apart, their titles don't have to be unique.
+    /// Clones keep it, so a page in `App::current_pages` is
+    /// found again in `App::pages`.
+    id: u64,
+}
+
+fn next_page_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The contents and title of a page after `text::normalize`.
+#[derive(Debug, Clone)]
+struct Normalized {
+    /// A hash of the text they were made from. The fields of
+    /// a page are public, so the text can change at any time.
+    source: u64,
+    contents: String,
+    title: String,
+}
+
+impl Normalized {
+    fn of(page: &Page) -> Self {
+        Self {
+            source: page.text_hash(),
+            contents: text::normalize(&page.contents),
+            title: text::normalize(&page.title),
+        }
+    }
 }
 
 impl Page {
@@ -105,15 +221,116 @@ impl Page {
             contents,
             title,
             sort_field,
+            links: Vec::new(),
+            language: None,
+            relevancy: 0,
+            contents_loaded: true,
+            marked: false,
+            normalized: None,
+            id: next_page_id(),
+        }
+    }
+
+    /// Creates a page whose contents are loaded by
+    /// `PageSource::load_contents` when it is first selected.
+    pub fn lazy(title: String, sort_field: Option<f64>) -> Self {
+        Self {
+            contents: String::new(),
+            title,
+            sort_field,
+            links: Vec::new(),
+            language: None,
             relevancy: 0,
+            contents_loaded: false,
+            marked: false,
+            normalized: None,
+            id: next_page_id(),
+        }
+    }
+
+    pub fn with_links(mut self, links: Vec<Link>) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    /// Whether the user has marked this page in the dock.
+    pub fn is_marked(&self) -> bool {
+        self.marked
+    }
+
+    /// How relevant the page was to the last search.
+    /// Zero if it didn't match or there was no search.
+    pub fn relevancy(&self) -> u64 {
+        self.relevancy
+    }
+
+    pub(crate) fn set_contents(&mut self, contents: String) {
+        self.contents = contents;
+        self.contents_loaded = true;
+    }
+
+    fn text_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.contents.hash(&mut hasher);
+        self.title.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The cached normalized text if it is still up to date.
+    fn cached_normalized(&self) -> Option<&Normalized> {
+        self.normalized
+            .as_ref()
+            .filter(|normalized| normalized.source == self.text_hash())
+    }
+
+    /// `needle` is expected to be normalized already.
+    fn search(&mut self, needle: &str, keywords: Vec<&str>, config: &SearchConfig) {
+        if self.cached_normalized().is_none() {
+            self.normalized = Some(Normalized::of(self));
+        }
+        let normalized = self.normalized.as_ref().expect("normalized above");
+        let terms = self.score_terms(normalized, needle, keywords, config);
+        self.relevancy = terms.iter().map(|t| t.score).sum();
+    }
+
+    /// Breaks the relevancy of this page for `needle` down into
+    /// what each combination of its words added. Combinations
+    /// that don't match are left out. `needle` is normalized
+    /// like the search does, so its case and accents don't matter.
+    pub fn explain(
+        &self,
+        needle: &str,
+        keywords: Vec<&str>,
+        config: &SearchConfig,
+    ) -> Vec<ScoreTerm> {
+        let needle = &text::normalize(needle.trim());
+        match self.cached_normalized() {
+            Some(normalized) => self.score_terms(normalized, needle, keywords, config),
+            None => self.score_terms(&Normalized::of(self), needle, keywords, config),
         }
     }
 
-    fn search(&mut self, needle: &str, keywords: Vec<&str>) {
-        let haystack = &self.contents;
-        let mut relevancy = 0;
+    fn score_terms(
+        &self,
+        normalized: &Normalized,
+        needle: &str,
+        keywords: Vec<&str>,
+        config: &SearchConfig,
+    ) -> Vec<ScoreTerm> {
+        let haystack = &normalized.contents;
+        let title = &normalized.title;
+        let keywords = keywords
+            .iter()
+            .map(|k| text::normalize(k))
+            .collect::<Vec<String>>();
+        let mut terms = Vec::new();
 
-        let words = needle.split(' ').collect::<Vec<&str>>();
+        let words = needle.split_whitespace().collect::<Vec<&str>>();
 
         let mut combinations = Vec::new();
 
@@ -122,13 +339,7 @@ impl Page {
         for k in min_combs..max_combs {
             let combs = words.iter().combinations(k);
             for c in combs {
-                let combination = c
-                    .iter()
-                    .map(|w| w.to_string())
-                    .join(" ")
-                    .to_lowercase()
-                    .trim()
-                    .to_string();
+                let combination = c.iter().map(|w| w.to_string()).join(" ");
 
                 // This check might be unnecessary
                 if !combinations.contains(&combination) {
@@ -138,25 +349,141 @@ impl Page {
         }
 
         for comb in combinations {
-            if haystack.contains(&comb) {
-                let needle_size_multiplier = (comb.split(' ').count() as u64).pow(5);
+            // Lazy pages might not have contents yet so
+            // a match in the title is enough.
+            if haystack.contains(&comb) || title.contains(&comb) {
+                let needle_size_multiplier =
+                    (comb.split(' ').count() as u64).saturating_pow(config.size_exponent);
 
-                let keyword_multiplier = if keywords.contains(&comb.as_str()) {
-                    10
+                let keyword_multiplier = if keywords.contains(&comb) {
+                    config.keyword_multiplier
                 } else {
                     1
                 };
 
                 let count_relevancy = haystack.matches(&comb).count() as u64;
 
-                let title_relevancy = 25 * self.title.matches(&comb).count() as u64;
+                let title_matches = title.matches(&comb).count() as u64;
+                let title_relevancy = config.title_weight.saturating_mul(title_matches);
 
-                relevancy += keyword_multiplier
-                    * needle_size_multiplier
-                    * (count_relevancy + title_relevancy);
+                terms.push(ScoreTerm {
+                    score: keyword_multiplier
+                        .saturating_mul(needle_size_multiplier)
+                        .saturating_mul(count_relevancy.saturating_add(title_relevancy)),
+                    combination: comb,
+                    needle_size_multiplier,
+                    keyword_multiplier,
+                    count_relevancy,
+                    title_relevancy,
+                });
             }
         }
 
-        self.relevancy = relevancy;
+        terms
+    }
+}
+
+/// A reference from one page to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// What the link is called in the contents. Highlighted
+    /// in the reader while the link is selected.
+    pub text: String,
+    /// The title of the page the link leads to.
+    pub target: String,
+}
+
+impl Link {
+    pub fn new(text: &str, target: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            target: target.to_string(),
+        }
+    }
+}
+
+/// What one combination of search words added to the relevancy
+/// of a page: `keyword_multiplier * needle_size_multiplier *
+/// (count_relevancy + title_relevancy)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreTerm {
+    pub combination: String,
+    /// Grows with the number of words so longer matches win.
+    pub needle_size_multiplier: u64,
+    /// Higher if the combination is one of `Opts::get_keywords`.
+    pub keyword_multiplier: u64,
+    /// How often the combination appears in the contents.
+    pub count_relevancy: u64,
+    /// How often it appears in the title, weighted.
+    pub title_relevancy: u64,
+    pub score: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relevancy(page: &mut Page, needle: &str) -> u64 {
+        page.search(
+            &text::normalize(needle),
+            Vec::new(),
+            &SearchConfig::default(),
+        );
+        page.relevancy()
+    }
+
+    #[test]
+    fn search_counts_contents_and_title_matches() {
+        let config = SearchConfig::default();
+        let mut page = Page::new(
+            String::from("Cargo builds crates, cargo runs them"),
+            String::from("Cargo"),
+            None,
+        );
+
+        assert_eq!(relevancy(&mut page, "cargo"), 2 + config.title_weight);
+        assert_eq!(relevancy(&mut page, "rustup"), 0);
+    }
+
+    #[test]
+    fn search_matches_normalized_text() {
+        let mut page = Page::new(String::from("Ｃａｆé"), String::from("Menu"), None);
+        assert_eq!(relevancy(&mut page, "CAFE"), 1);
+    }
+
+    #[test]
+    fn explain_normalizes_the_needle() {
+        let page = Page::new(String::from("Café au lait"), String::from("Menu"), None);
+        let terms = page.explain("CAFE", Vec::new(), &SearchConfig::default());
+
+        assert_eq!(terms.len(), 1);
+        assert_eq!(terms[0].combination, "cafe");
+        assert_eq!(terms[0].score, 1);
+    }
+
+    #[test]
+    fn search_sees_loaded_contents() {
+        let mut page = Page::lazy(String::from("Menu"), None);
+        assert_eq!(relevancy(&mut page, "cafe"), 0);
+
+        page.set_contents(String::from("cafe"));
+        assert_eq!(relevancy(&mut page, "cafe"), 1);
+    }
+
+    #[test]
+    fn search_sees_changed_fields() {
+        let mut page = Page::new(String::from("tea"), String::from("Menu"), None);
+        assert_eq!(relevancy(&mut page, "cafe"), 0);
+
+        page.contents = String::from("café");
+        assert_eq!(relevancy(&mut page, "cafe"), 1);
+        page.title = String::from("Cafe");
+        assert!(relevancy(&mut page, "cafe") > 1);
+    }
+
+    #[test]
+    fn pages_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Page>();
     }
 }
@@ -0,0 +1,119 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cmp::Reverse;
+
+use crate::command::{Builtin, Command};
+use crate::text;
+
+/// What runs when an entry is chosen.
+#[derive(Debug, Clone)]
+pub enum Target {
+    Builtin(Builtin),
+    /// Passed to `Opts::run_command` by name.
+    Opts,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub command: Command,
+    pub target: Target,
+}
+
+pub enum PaletteEvent {
+    Nothing,
+    Close,
+    Run(Entry, Option<String>),
+}
+
+/// State of the command palette while it is open.
+#[derive(Clone, Default)]
+pub struct Palette {
+    /// What is typed to filter the commands, or the
+    /// argument once a command that needs one is chosen.
+    pub input: Vec<char>,
+    pub selected: usize,
+    /// The chosen command while its argument is typed.
+    pub awaiting_argument: Option<Entry>,
+}
+
+pub fn entries(opts_commands: Vec<Command>) -> Vec<Entry> {
+    let builtins = Builtin::ALL.iter().map(|builtin| Entry {
+        command: builtin.command(),
+        target: Target::Builtin(*builtin),
+    });
+    let opts = opts_commands.into_iter().map(|command| Entry {
+        command,
+        target: Target::Opts,
+    });
+
+    builtins.chain(opts).collect()
+}
+
+impl Palette {
+    /// The entries matching the input, best match first.
+    pub fn filter(&self, entries: Vec<Entry>) -> Vec<Entry> {
+        let needle = self.input.iter().collect::<String>();
+        let mut scored = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let haystack = format!("{} {}", entry.command.name, entry.command.description);
+                text::fuzzy_score(&needle, &haystack).map(|score| (score, entry))
+            })
+            .collect::<Vec<(u32, Entry)>>();
+
+        // Stable, so equally good matches keep their order.
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, entries: Vec<Entry>) -> PaletteEvent {
+        let filtered_len = if self.awaiting_argument.is_some() {
+            0
+        } else {
+            self.filter(entries.clone()).len()
+        };
+
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) => return PaletteEvent::Close,
+            (KeyModifiers::CONTROL, KeyCode::Char('k' | 'p')) | (_, KeyCode::Up) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('j' | 'n')) | (_, KeyCode::Down)
+                if self.selected + 1 < filtered_len =>
+            {
+                self.selected += 1
+            }
+            (_, KeyCode::Backspace) => {
+                text::pop_grapheme(&mut self.input);
+                self.selected = 0;
+            }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => {
+                self.input.push(c);
+                self.selected = 0;
+            }
+            (_, KeyCode::Enter) => return self.choose(entries),
+            _ => {}
+        }
+
+        PaletteEvent::Nothing
+    }
+
+    fn choose(&mut self, entries: Vec<Entry>) -> PaletteEvent {
+        if let Some(entry) = self.awaiting_argument.take() {
+            let argument = self.input.iter().collect();
+            return PaletteEvent::Run(entry, Some(argument));
+        }
+
+        let entry = match self.filter(entries).into_iter().nth(self.selected) {
+            Some(entry) => entry,
+            None => return PaletteEvent::Nothing,
+        };
+
+        if entry.command.argument.is_some() {
+            self.input.clear();
+            self.awaiting_argument = Some(entry);
+            PaletteEvent::Nothing
+        } else {
+            PaletteEvent::Run(entry, None)
+        }
+    }
+}
@@ -0,0 +1,505 @@
+use std::{
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+use crate::command::Builtin;
+use crate::error::Result;
+use crate::keybind::{self, Keybind};
+use crate::{App, Opts};
+
+/// Set to use the line based interface even in a terminal,
+/// e.g. when using a screen reader.
+pub const PLAIN_ENV_VAR: &str = "TUI_VIEW_PLAIN";
+
+/// How many pages `search` lists before asking to use `list`.
+const SEARCH_RESULTS: usize = 10;
+
+const HELP: &str = "\
+help                 Show this help
+search <text>        Search, or clear the search without text. Same as /<text>
+list [count]         List the pages matching the search
+show [number]        Show a page, the selected one without a number
+<number>             Same as show <number>
+next, previous       Show the next or previous page
+links                List the links of the selected page
+follow <number>      Show the page a link leads to
+back, forward        Show the page before or after in the history of followed links
+mark [number]        Mark or unmark a page, the selected one without a number
+mark all             Mark all pages matching the search, or unmark them
+marked               List the marked pages
+commands             List the commands of this app
+run <name> [text]    Run a command by name, with text as its argument
+key <keys>           Press keys of this app, e.g. key <C-q>
+quit                 Exit";
+
+/// Runs the app as a prompt that reads commands line by line from
+/// `input` and writes plain text to `output`. Offers the same things
+/// as the full screen view for screen readers and scripts.
+pub fn run_plain<R: BufRead, W: Write>(opts: Rc<dyn Opts>, input: R, output: W) -> Result<()> {
+    let mut repl = Repl {
+        app: App::new(opts.clone()),
+        opts,
+        output,
+    };
+
+    writeln!(
+        repl.output,
+        "{} pages. Type help for the list of commands.",
+        repl.app.current_pages.len()
+    )?;
+
+    let mut lines = input.lines();
+    loop {
+        write!(repl.output, "> ")?;
+        repl.output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let line = line.trim();
+        let (command, argument) = match line.strip_prefix('/') {
+            Some(search) => ("search", search),
+            None => line.split_once(' ').unwrap_or((line, "")),
+        };
+
+        if !repl.run(command, argument.trim())? {
+            break;
+        }
+        repl.report_popup()?;
+    }
+
+    writeln!(repl.output)?;
+    Ok(())
+}
+
+struct Repl<W: Write> {
+    app: App,
+    opts: Rc<dyn Opts>,
+    output: W,
+}
+
+impl<W: Write> Repl<W> {
+    /// Returns false once the user wants to exit.
+    fn run(&mut self, command: &str, argument: &str) -> Result<bool> {
+        match command {
+            "" => {}
+            "help" | "?" => self.help()?,
+            "quit" | "exit" | "q" => return Ok(false),
+            "search" => self.search(argument)?,
+            "list" | "ls" => match argument.parse() {
+                Ok(count) => self.list(count)?,
+                Err(_) => self.list(self.app.current_pages.len())?,
+            },
+            "show" => self.show(argument)?,
+            "next" | "n" => {
+                self.app.next();
+                self.show("")?;
+            }
+            "previous" | "p" => {
+                self.app.previous();
+                self.show("")?;
+            }
+            "links" => self.links()?,
+            "follow" => self.follow(argument)?,
+            "back" => {
+                self.app.go_back();
+                self.show("")?;
+            }
+            "forward" => {
+                self.app.go_forward();
+                self.show("")?;
+            }
+            "mark" => self.mark(argument)?,
+            "marked" => self.marked()?,
+            "commands" => self.commands()?,
+            "run" => return self.run_command(argument),
+            "key" => self.key(argument)?,
+            number if number.parse::<usize>().is_ok() => self.show(number)?,
+            unknown => writeln!(
+                self.output,
+                "Unknown command {}. Type help for the list of commands.",
+                unknown
+            )?,
+        }
+        Ok(true)
+    }
+
+    fn help(&mut self) -> Result<()> {
+        writeln!(self.output, "{}", HELP)?;
+
+        let keybinds = self.opts.describe_keybinds();
+        if !keybinds.is_empty() {
+            writeln!(self.output, "\nKeys of this app:")?;
+            self.keybinds(keybinds)?;
+        }
+        Ok(())
+    }
+
+    fn keybinds(&mut self, keybinds: Vec<Keybind>) -> Result<()> {
+        for keybind in keybinds {
+            writeln!(self.output, "{}: {}", keybind.keys, keybind.description)?;
+        }
+        Ok(())
+    }
+
+    fn search(&mut self, text: &str) -> Result<()> {
+        self.app.search = text.chars().collect();
+        self.app.apply_search();
+
+        let found = self.app.current_pages.len();
+        writeln!(self.output, "{} pages found.", found)?;
+        self.list(SEARCH_RESULTS)?;
+        if found > SEARCH_RESULTS {
+            writeln!(self.output, "Type list to see all of them.")?;
+        }
+        Ok(())
+    }
+
+    fn list(&mut self, count: usize) -> Result<()> {
+        for (i, page) in self.app.current_pages.iter().take(count).enumerate() {
+            let mark = if page.is_marked() { ", marked" } else { "" };
+            writeln!(self.output, "{}. {}{}", i + 1, page.title, mark)?;
+        }
+        Ok(())
+    }
+
+    /// Parses a page number as shown by `list`, or takes the
+    /// selected page if there is no number.
+    fn page_index(&mut self, number: &str) -> Result<Option<usize>> {
+        let index = match number {
+            "" => self.app.state.selected(),
+            number => number.parse::<usize>().ok().and_then(|n| n.checked_sub(1)),
+        };
+
+        match index {
+            Some(i) if i < self.app.current_pages.len() => Ok(Some(i)),
+            _ => {
+                writeln!(self.output, "No such page. Type list to see the pages.")?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn show(&mut self, number: &str) -> Result<()> {
+        let i = match self.page_index(number)? {
+            Some(i) => i,
+            None => return Ok(()),
+        };
+        self.app.state.select(Some(i));
+        self.app.load_contents();
+        if self.app.show_popup {
+            // The error is printed like any other popup.
+            return Ok(());
+        }
+
+        let page = match self.app.selected_page() {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        // There is no screen to update later, so the preview is waited for.
+        let contents = match self.opts.preview(page) {
+            Some(job) => job(),
+            None => page.contents.clone(),
+        };
+
+        writeln!(self.output, "Page {}: {}", i + 1, page.title)?;
+        writeln!(self.output, "{}", contents)?;
+        writeln!(self.output, "End of page {}.", i + 1)?;
+        Ok(())
+    }
+
+    fn links(&mut self) -> Result<()> {
+        let links = match self.app.selected_page() {
+            Some(page) => page.links.clone(),
+            None => Vec::new(),
+        };
+        writeln!(self.output, "{} links.", links.len())?;
+        for (i, link) in links.iter().enumerate() {
+            writeln!(self.output, "{}. {} to {}", i + 1, link.text, link.target)?;
+        }
+        Ok(())
+    }
+
+    fn follow(&mut self, number: &str) -> Result<()> {
+        let index = number.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+        match index {
+            Some(i) if self.app.select_link(i) => {
+                self.app.follow_link();
+                self.show("")
+            }
+            _ => {
+                writeln!(self.output, "No such link. Type links to see them.")?;
+                Ok(())
+            }
+        }
+    }
+
+    fn mark(&mut self, number: &str) -> Result<()> {
+        if number == "all" {
+            self.app.toggle_mark_all();
+            let marked = self.app.marked_pages().len();
+            writeln!(self.output, "{} pages marked.", marked)?;
+            return Ok(());
+        }
+
+        if let Some(i) = self.page_index(number)? {
+            self.app.toggle_mark_at(i);
+            let page = &self.app.current_pages[i];
+            let state = if page.is_marked() {
+                "Marked"
+            } else {
+                "Unmarked"
+            };
+            writeln!(self.output, "{} {}.", state, page.title)?;
+        }
+        Ok(())
+    }
+
+    fn marked(&mut self) -> Result<()> {
+        let marked = self.app.marked_pages();
+        writeln!(self.output, "{} pages marked.", marked.len())?;
+        for page in marked {
+            writeln!(self.output, "{}", page.title)?;
+        }
+        Ok(())
+    }
+
+    fn commands(&mut self) -> Result<()> {
+        let commands = self.opts.commands();
+        if commands.is_empty() {
+            writeln!(self.output, "This app has no commands.")?;
+        }
+        for command in commands {
+            let argument = match &command.argument {
+                Some(prompt) => format!(" <{}>", prompt.to_lowercase()),
+                None => String::new(),
+            };
+            writeln!(
+                self.output,
+                "{}{}: {}",
+                command.name, argument, command.description
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Runs a command of the app, or a built-in one that makes sense
+    /// without a screen. Returns false if the command was exit.
+    fn run_command(&mut self, text: &str) -> Result<bool> {
+        let (name, argument) = text.split_once(' ').unwrap_or((text, ""));
+        let argument = match argument.trim() {
+            "" => None,
+            argument => Some(argument.to_string()),
+        };
+
+        if let Some(command) = self.opts.commands().into_iter().find(|c| c.name == name) {
+            if command.argument.is_some() && argument.is_none() {
+                writeln!(self.output, "{} needs an argument.", command.name)?;
+                return Ok(true);
+            }
+            self.app = self
+                .opts
+                .run_command(&command.name, argument, self.app.clone());
+            writeln!(self.output, "Done.")?;
+            return Ok(true);
+        }
+
+        let builtin = Builtin::ALL
+            .iter()
+            .find(|builtin| builtin.command().name == name);
+        match builtin {
+            Some(Builtin::Exit) => return Ok(false),
+            Some(Builtin::Next) => {
+                self.run("next", "")?;
+            }
+            Some(Builtin::Previous) => {
+                self.run("previous", "")?;
+            }
+            Some(Builtin::ToggleMark) => {
+                self.run("mark", "")?;
+            }
+            Some(Builtin::ToggleMarkAll) => {
+                self.run("mark", "all")?;
+            }
+            Some(Builtin::Search) => {
+                self.run("search", &argument.unwrap_or_default())?;
+            }
+            Some(Builtin::Help) => {
+                self.run("help", "")?;
+            }
+            Some(Builtin::Back) => {
+                self.run("back", "")?;
+            }
+            Some(Builtin::Forward) => {
+                self.run("forward", "")?;
+            }
+            Some(_) => writeln!(self.output, "{} is not available here.", name)?,
+            None => writeln!(
+                self.output,
+                "Unknown command {}. Type commands to see them.",
+                name
+            )?,
+        };
+        Ok(true)
+    }
+
+    fn key(&mut self, keys: &str) -> Result<()> {
+        match keybind::parse_keys(keys) {
+            Some(key) => {
+                self.app = self.opts.keybinds(key, self.app.clone());
+                writeln!(self.output, "Done.")?;
+            }
+            None => writeln!(self.output, "Could not read the keys {}.", keys)?,
+        }
+        Ok(())
+    }
+
+    /// Popups are how apps report results, so they are printed
+    /// and closed after every command.
+    fn report_popup(&mut self) -> Result<()> {
+        if self.app.show_popup {
+            writeln!(self.output, "{}", self.app.popup_content)?;
+            self.app.show_popup = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::*;
+    use crate::{Command, Link, Page};
+
+    struct Shelf;
+
+    impl Opts for Shelf {
+        fn get_pages(&self) -> Vec<Page> {
+            let mut cargo = Page::new(
+                String::from("Builds rust code"),
+                String::from("cargo"),
+                None,
+            );
+            cargo.links = vec![Link::new("the installer", "rustup")];
+            vec![
+                cargo,
+                Page::new(String::from("Installs rust"), String::from("rustup"), None),
+                Page::new(String::from("Formats code"), String::from("rustfmt"), None),
+            ]
+        }
+
+        fn keybinds(&self, key: KeyEvent, mut app: App) -> App {
+            if key == KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL) {
+                app.popup_content = String::from("Reloaded");
+                app.show_popup = true;
+            }
+            app
+        }
+
+        fn describe_keybinds(&self) -> Vec<Keybind> {
+            vec![Keybind::new("<C-r>", "Reload")]
+        }
+
+        fn commands(&self) -> Vec<Command> {
+            vec![Command::new("greet", "Say hello").with_argument("Name")]
+        }
+
+        fn run_command(&self, _name: &str, argument: Option<String>, mut app: App) -> App {
+            app.popup_content = format!("Hello {}", argument.unwrap_or_default());
+            app.show_popup = true;
+            app
+        }
+    }
+
+    /// Runs the lines as a session and returns what was printed,
+    /// without the prompts.
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        run_plain(Rc::new(Shelf), input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().replace("> ", "")
+    }
+
+    #[test]
+    fn lists_and_searches_pages() {
+        assert_eq!(
+            session("list\n/rust\nsearch\nlist 1\n"),
+            "3 pages. Type help for the list of commands.\n\
+             1. cargo\n2. rustup\n3. rustfmt\n\
+             3 pages found.\n1. rustup\n2. rustfmt\n3. cargo\n\
+             3 pages found.\n1. cargo\n2. rustup\n3. rustfmt\n\
+             1. cargo\n\n"
+        );
+    }
+
+    #[test]
+    fn shows_pages_by_number() {
+        assert_eq!(
+            session("show 2\nnext\n9\n"),
+            "3 pages. Type help for the list of commands.\n\
+             Page 2: rustup\nInstalls rust\nEnd of page 2.\n\
+             Page 3: rustfmt\nFormats code\nEnd of page 3.\n\
+             No such page. Type list to see the pages.\n\n"
+        );
+    }
+
+    #[test]
+    fn follows_links_and_goes_back() {
+        let output = session("1\nlinks\nfollow 1\nback\nfollow 2\n");
+        let expected = "\
+            1 links.\n1. the installer to rustup\n\
+            Page 2: rustup\nInstalls rust\nEnd of page 2.\n\
+            Page 1: cargo\nBuilds rust code\nEnd of page 1.\n\
+            No such link. Type links to see them.\n\n";
+        assert!(output.ends_with(expected), "{}", output);
+    }
+
+    #[test]
+    fn marks_pages() {
+        let output = session("mark 3\nmarked\nlist\nmark 3\nmark all\n");
+        let expected = "\
+            Marked rustfmt.\n1 pages marked.\nrustfmt\n\
+            1. cargo\n2. rustup\n3. rustfmt, marked\n\
+            Unmarked rustfmt.\n3 pages marked.\n\n";
+        assert!(output.ends_with(expected), "{}", output);
+    }
+
+    #[test]
+    fn runs_commands_and_prints_their_popups() {
+        let output = session("commands\nrun greet\nrun greet Ferris\nrun next\nrun nope\n");
+        let expected = "\
+            greet <name>: Say hello\n\
+            greet needs an argument.\n\
+            Done.\nHello Ferris\n\
+            Page 1: cargo\nBuilds rust code\nEnd of page 1.\n\
+            Unknown command nope. Type commands to see them.\n\n";
+        assert!(output.ends_with(expected), "{}", output);
+    }
+
+    #[test]
+    fn presses_keys_of_the_app() {
+        let output = session("key <C-r>\nkey <Nope>\n");
+        let expected = "\
+            Done.\nReloaded\n\
+            Could not read the keys <Nope>.\n\n";
+        assert!(output.ends_with(expected), "{}", output);
+    }
+
+    #[test]
+    fn help_lists_the_keys_of_the_app() {
+        let output = session("help\n");
+        assert!(output.contains("quit                 Exit\n"));
+        assert!(output.ends_with("Keys of this app:\n<C-r>: Reload\n\n"));
+    }
+
+    #[test]
+    fn stops_at_quit_or_the_end_of_the_input() {
+        assert_eq!(
+            session("quit\nlist\n"),
+            "3 pages. Type help for the list of commands.\n\n"
+        );
+        assert!(session("wat\n").contains("Unknown command wat."));
+    }
+}
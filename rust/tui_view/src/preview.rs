@@ -0,0 +1,257 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Work returned by `Opts::preview` that computes the preview
+/// of a page. It is run on a background thread so it has to own
+/// everything it needs.
+pub type PreviewJob = Box<dyn FnOnce() -> String + Send>;
+
+/// How long the selection has to stay on a page
+/// before its preview is requested.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+struct Request {
+    title: String,
+    generation: u64,
+    job: PreviewJob,
+}
+
+/// A finished preview and the generation it was requested in.
+struct Finished {
+    title: String,
+    generation: u64,
+    preview: String,
+}
+
+struct Worker {
+    requests: Sender<Request>,
+    results: Receiver<Finished>,
+}
+
+impl Worker {
+    fn spawn(generation: Arc<AtomicU64>) -> Self {
+        let (requests, request_receiver) = mpsc::channel::<Request>();
+        let (result_sender, results) = mpsc::channel();
+
+        // Exits once the app, and with it the sender, is dropped.
+        thread::spawn(move || {
+            for request in request_receiver {
+                // The selection moved on before this one was started.
+                if request.generation != generation.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let finished = Finished {
+                    preview: (request.job)(),
+                    title: request.title,
+                    generation: request.generation,
+                };
+                if result_sender.send(finished).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { requests, results }
+    }
+}
+
+/// Previews keyed by page title. `None` means `Opts::preview`
+/// didn't provide one and the contents are shown instead.
+pub struct Previews {
+    cache: HashMap<String, Option<String>>,
+    /// The page waiting for the debounce to pass and since when.
+    waiting: Option<(String, Instant)>,
+    in_flight: Option<String>,
+    /// Bumped whenever the selection changes so queued
+    /// requests for other pages are skipped.
+    generation: Arc<AtomicU64>,
+    /// The generation `clear` was last called in. Previews
+    /// requested before that are for contents that changed.
+    cleared: u64,
+    worker: Option<Worker>,
+}
+
+impl Previews {
+    pub fn new() -> Self {
+        Self {
+            cache: HashMap::new(),
+            waiting: None,
+            in_flight: None,
+            generation: Arc::new(AtomicU64::new(0)),
+            cleared: 0,
+            worker: None,
+        }
+    }
+
+    pub fn get(&self, title: &str) -> Option<&String> {
+        self.cache.get(title).and_then(|preview| preview.as_ref())
+    }
+
+    /// Whether the preview of `title` is being computed.
+    pub fn is_loading(&self, title: &str) -> bool {
+        self.in_flight.as_deref() == Some(title)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.waiting.is_some() || self.in_flight.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.waiting = None;
+        self.in_flight = None;
+        self.cleared = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    }
+
+    /// Collects finished previews and requests the one for the selected
+    /// page once it has been selected for long enough. `job` is only
+    /// called when the preview is actually requested.
+    pub fn update(&mut self, selected: Option<&str>, job: impl FnOnce() -> Option<PreviewJob>) {
+        if let Some(worker) = &self.worker {
+            for finished in worker.results.try_iter() {
+                if finished.generation < self.cleared {
+                    continue;
+                }
+                if self.in_flight.as_ref() == Some(&finished.title) {
+                    self.in_flight = None;
+                }
+                self.cache.insert(finished.title, Some(finished.preview));
+            }
+        }
+
+        let title = match selected {
+            Some(title) if !self.cache.contains_key(title) => title,
+            _ => {
+                self.waiting = None;
+                return;
+            }
+        };
+        if self.in_flight.as_deref() == Some(title) {
+            return;
+        }
+
+        match &self.waiting {
+            Some((waiting, since)) if waiting == title => {
+                if since.elapsed() < DEBOUNCE {
+                    return;
+                }
+            }
+            _ => {
+                // Whatever is in flight still gets cached when it
+                // arrives, but it is skipped if it hasn't started yet.
+                self.waiting = Some((title.to_string(), Instant::now()));
+                self.in_flight = None;
+                self.generation.fetch_add(1, Ordering::SeqCst);
+                return;
+            }
+        }
+
+        self.waiting = None;
+        let job = match job() {
+            Some(job) => job,
+            None => {
+                self.cache.insert(title.to_string(), None);
+                return;
+            }
+        };
+
+        let generation = Arc::clone(&self.generation);
+        let worker = self.worker.get_or_insert_with(|| Worker::spawn(generation));
+        let request = Request {
+            title: title.to_string(),
+            generation: self.generation.load(Ordering::SeqCst),
+            job,
+        };
+        if worker.requests.send(request).is_ok() {
+            self.in_flight = Some(title.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls `update` until `done` or a second has passed.
+    fn update_until(
+        previews: &mut Previews,
+        selected: Option<&str>,
+        job: impl Fn() -> Option<PreviewJob>,
+        done: impl Fn(&Previews) -> bool,
+    ) {
+        let started = Instant::now();
+        while !done(previews) && started.elapsed() < Duration::from_secs(1) {
+            previews.update(selected, &job);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn job(preview: &'static str) -> impl Fn() -> Option<PreviewJob> {
+        move || Some(Box::new(move || preview.to_string()) as PreviewJob)
+    }
+
+    #[test]
+    fn previews_the_selected_page_after_the_debounce() {
+        let mut previews = Previews::new();
+        previews.update(Some("cargo"), job("built"));
+        assert!(previews.is_busy());
+        assert_eq!(previews.get("cargo"), None);
+
+        update_until(&mut previews, Some("cargo"), job("built"), |p| {
+            p.get("cargo").is_some()
+        });
+        assert_eq!(previews.get("cargo").map(String::as_str), Some("built"));
+        assert!(!previews.is_busy());
+    }
+
+    #[test]
+    fn pages_without_a_preview_show_their_contents() {
+        let mut previews = Previews::new();
+        update_until(
+            &mut previews,
+            Some("cargo"),
+            || None,
+            |p| p.cache.contains_key("cargo"),
+        );
+        assert_eq!(previews.cache.get("cargo"), Some(&None));
+        assert!(!previews.is_busy());
+    }
+
+    #[test]
+    fn previews_from_before_clear_are_dropped() {
+        let (release, released) = mpsc::channel::<()>();
+        let released = std::sync::Mutex::new(Some(released));
+        let (finished, finished_receiver) = mpsc::channel::<()>();
+        let blocking_job = move || {
+            let released = released.lock().unwrap().take()?;
+            let finished = finished.clone();
+            Some(Box::new(move || {
+                let _ = released.recv();
+                let _ = finished.send(());
+                String::from("stale")
+            }) as PreviewJob)
+        };
+
+        let mut previews = Previews::new();
+        update_until(&mut previews, Some("cargo"), blocking_job, |p| {
+            p.is_loading("cargo")
+        });
+        assert!(previews.is_loading("cargo"));
+
+        previews.clear();
+        release.send(()).unwrap();
+        finished_receiver.recv().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        previews.update(None, || None);
+
+        assert_eq!(previews.get("cargo"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::{io::Write, rc::Rc};
+
+use crate::error::Result;
+use crate::{App, Error, Opts, Page};
+
+/// What to search and how to print it in `run_query`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub text: String,
+    /// Print the contents of the best match instead of the titles.
+    pub contents: bool,
+    pub json: bool,
+    /// Print at most this many titles.
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Reads `--query <text>` (or `--query=<text>`), `--contents`,
+    /// `--json` and `--limit <n>` from command line arguments.
+    /// Returns None if there is no `--query`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut query = Self::default();
+        let mut text = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--query" => text = args.next(),
+                "--contents" => query.contents = true,
+                "--json" => query.json = true,
+                "--limit" => query.limit = args.next().and_then(|n| n.parse().ok()),
+                _ => {
+                    if let Some(value) = arg.strip_prefix("--query=") {
+                        text = Some(value.to_string());
+                    } else if let Some(value) = arg.strip_prefix("--limit=") {
+                        query.limit = value.parse().ok();
+                    }
+                }
+            }
+        }
+
+        query.text = text?;
+        Some(query)
+    }
+}
+
+#[derive(Serialize)]
+struct QueryResult<'a> {
+    title: &'a str,
+    relevancy: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_field: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contents: Option<&'a str>,
+}
+
+impl<'a> QueryResult<'a> {
+    fn new(page: &'a Page, contents: Option<&'a str>) -> Self {
+        Self {
+            title: &page.title,
+            relevancy: page.relevancy,
+            sort_field: page.sort_field,
+            contents,
+        }
+    }
+}
+
+/// Searches the pages of `opts` the same way typing in the view does
+/// and writes the ranked titles, one per line, to `output`. All pages
+/// of a `PageSource` are fetched first.
+///
+/// With `query.contents` only the best match is written, with what
+/// the reader would show for it. With `query.json` the output is a
+/// JSON array of objects with the title and relevancy of each page.
+pub fn run_query<W: Write>(opts: Rc<dyn Opts>, query: &Query, mut output: W) -> Result<()> {
+    let mut app = App::new(opts.clone());
+    app.fetch_all()?;
+    app.search = query.text.chars().collect();
+    app.apply_search();
+
+    if query.contents {
+        app.load_contents();
+        if app.show_popup {
+            return Err(Error::Source(app.popup_content));
+        }
+        let page = match app.selected_page() {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        let contents = match opts.preview(page) {
+            Some(job) => job(),
+            None => page.contents.clone(),
+        };
+
+        if query.json {
+            let result = [QueryResult::new(page, Some(&contents))];
+            serde_json::to_writer_pretty(&mut output, &result).map_err(std::io::Error::from)?;
+            writeln!(output)?;
+        } else {
+            writeln!(output, "{}", contents)?;
+        }
+        return Ok(());
+    }
+
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let pages = app.current_pages.iter().take(limit);
+    if query.json {
+        let results = pages
+            .map(|page| QueryResult::new(page, None))
+            .collect::<Vec<_>>();
+        serde_json::to_writer_pretty(&mut output, &results).map_err(std::io::Error::from)?;
+        writeln!(output)?;
+    } else {
+        for page in pages {
+            writeln!(output, "{}", page.title)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Option<Query> {
+        Query::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn from_args_needs_a_query() {
+        assert_eq!(args(&[]), None);
+        assert_eq!(args(&["--json", "--limit", "3"]), None);
+        assert_eq!(args(&["--query"]), None);
+    }
+
+    #[test]
+    fn from_args_reads_every_flag() {
+        assert_eq!(
+            args(&["--query", "cargo", "--contents", "--json", "--limit", "3"]),
+            Some(Query {
+                text: String::from("cargo"),
+                contents: true,
+                json: true,
+                limit: Some(3),
+            })
+        );
+        assert_eq!(
+            args(&["--query=rust up", "--limit=2", "other"]),
+            Some(Query {
+                limit: Some(2),
+                ..Query::new("rust up")
+            })
+        );
+    }
+
+    struct Tools;
+
+    impl Opts for Tools {
+        fn get_pages(&self) -> Vec<Page> {
+            vec![
+                Page::new(
+                    String::from("Builds rust code"),
+                    String::from("cargo"),
+                    None,
+                ),
+                Page::new(String::from("Installs rust"), String::from("rustup"), None),
+                Page::new(String::from("Formats code"), String::from("rustfmt"), None),
+            ]
+        }
+    }
+
+    fn output(query: Query) -> String {
+        let mut output = Vec::new();
+        run_query(Rc::new(Tools), &query, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn prints_ranked_titles() {
+        assert_eq!(output(Query::new("rust")), "rustup\nrustfmt\ncargo\n");
+        let first = Query {
+            limit: Some(1),
+            ..Query::new("rust")
+        };
+        assert_eq!(output(first), "rustup\n");
+        assert_eq!(output(Query::new("python")), "");
+    }
+
+    #[test]
+    fn prints_the_contents_of_the_best_match() {
+        let query = Query {
+            contents: true,
+            ..Query::new("format")
+        };
+        assert_eq!(output(query), "Formats code\n");
+    }
+
+    #[test]
+    fn prints_json() {
+        let query = Query {
+            json: true,
+            limit: Some(1),
+            ..Query::new("install")
+        };
+        let results: serde_json::Value = serde_json::from_str(&output(query)).unwrap();
+        assert_eq!(
+            results,
+            serde_json::json!([{ "title": "rustup", "relevancy": 1 }])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::cmp::Ordering;
+
+use crate::Page;
+
+/// How the search scores pages and sorts the ones that matched.
+/// Returned from `Opts::search_config`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchConfig {
+    /// Multiplies how often a combination of searched words
+    /// appears in the title.
+    pub title_weight: u64,
+    /// Multiplies the score of a combination that is one
+    /// of `Opts::get_keywords`.
+    pub keyword_multiplier: u64,
+    /// The number of words in a combination is raised to this
+    /// power, so matches of longer phrases weigh more.
+    pub size_exponent: u32,
+    pub order: SortOrder,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            title_weight: 25,
+            keyword_multiplier: 10,
+            size_exponent: 5,
+            order: SortOrder::SortField,
+        }
+    }
+}
+
+/// How matching pages are sorted. Both are descending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// By `Page::sort_field` and by relevancy where that is equal.
+    /// Pages without a sort field come last, so pages are only
+    /// sorted by relevancy if none of them have one.
+    SortField,
+    /// By relevancy and by `Page::sort_field` where that is equal.
+    Relevancy,
+}
+
+impl SearchConfig {
+    /// The order of two pages in the search results.
+    /// NaN sort fields count as missing.
+    pub fn compare(&self, a: &Page, b: &Page) -> Ordering {
+        let by_sort_field = compare_sort_fields(b.sort_field, a.sort_field);
+        let by_relevancy = b.relevancy().cmp(&a.relevancy());
+
+        match self.order {
+            SortOrder::SortField => by_sort_field.then(by_relevancy),
+            SortOrder::Relevancy => by_relevancy.then(by_sort_field),
+        }
+    }
+}
+
+fn compare_sort_fields(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a.filter(|f| !f.is_nan()), b.filter(|f| !f.is_nan())) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, sort_field: Option<f64>, relevancy: u64) -> Page {
+        let mut page = Page::new(String::new(), String::from(title), sort_field);
+        page.relevancy = relevancy;
+        page
+    }
+
+    fn sorted(config: &SearchConfig, mut pages: Vec<Page>) -> Vec<String> {
+        pages.sort_by(|a, b| config.compare(a, b));
+        pages.into_iter().map(|page| page.title).collect()
+    }
+
+    #[test]
+    fn nan_sort_fields_count_as_missing() {
+        let config = SearchConfig::default();
+        let pages = vec![
+            page("nan", Some(f64::NAN), 3),
+            page("none", None, 2),
+            page("one", Some(1.0), 0),
+            page("two", Some(2.0), 0),
+        ];
+
+        assert_eq!(sorted(&config, pages), ["two", "one", "nan", "none"]);
+        assert_eq!(compare_sort_fields(Some(f64::NAN), None), Ordering::Equal);
+        assert_eq!(
+            compare_sort_fields(Some(f64::NAN), Some(f64::NEG_INFINITY)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn equal_sort_fields_are_ordered_by_relevancy() {
+        let config = SearchConfig::default();
+        let pages = vec![
+            page("low", Some(1.0), 1),
+            page("high", Some(1.0), 5),
+            page("first", Some(2.0), 0),
+        ];
+
+        assert_eq!(sorted(&config, pages), ["first", "high", "low"]);
+    }
+
+    #[test]
+    fn equal_relevancy_is_ordered_by_sort_field() {
+        let config = SearchConfig {
+            order: SortOrder::Relevancy,
+            ..SearchConfig::default()
+        };
+        let pages = vec![
+            page("nan", Some(f64::NAN), 2),
+            page("low", Some(1.0), 2),
+            page("high", Some(3.0), 2),
+            page("first", None, 7),
+        ];
+
+        assert_eq!(sorted(&config, pages), ["first", "high", "low", "nan"]);
+    }
+
+    #[test]
+    fn pages_equal_in_both_keep_their_order() {
+        let config = SearchConfig::default();
+        let pages = vec![
+            page("a", Some(f64::NAN), 4),
+            page("b", None, 4),
+            page("c", Some(f64::NAN), 4),
+        ];
+
+        assert_eq!(sorted(&config, pages), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::Page;
+
+pub type SourceResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// A source that pages are loaded from as they are needed
+/// instead of all at once. Useful for remote data like search
+/// results from an API.
+pub trait PageSource {
+    /// Returns up to `count` pages starting from `offset`.
+    /// Returning less than `count` pages means there are no more.
+    fn fetch(&self, offset: usize, count: usize) -> SourceResult<Vec<Page>>;
+
+    /// How many pages to ask for at a time. More pages are fetched
+    /// when the selection gets close to the end of the dock. A source
+    /// with a page size of 0 is taken to have no pages.
+    fn page_size(&self) -> usize {
+        50
+    }
+
+    /// Called when a page created with `Page::lazy` is selected
+    /// for the first time. The returned string becomes its contents.
+    fn load_contents(&self, _page: &Page) -> SourceResult<String> {
+        Ok(String::new())
+    }
+}
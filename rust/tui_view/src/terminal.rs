@@ -0,0 +1,246 @@
+use crossterm::{
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use signal_hook::{consts::signal, flag, low_level, SigId};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Whether the terminal is currently in raw mode on the alternate screen.
+/// Shared with the panic hook so the terminal is only restored once.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal into raw mode on the alternate screen and
+/// restores it when dropped, even while unwinding from a panic.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new() -> io::Result<Self> {
+        // Marked active before anything is changed so a failure
+        // half way through still gets cleaned up by drop.
+        ACTIVE.store(true, Ordering::SeqCst);
+        let guard = Self;
+        enter()?;
+        Ok(guard)
+    }
+
+    /// Same as dropping the guard but reports the errors.
+    pub fn restore(self) -> io::Result<()> {
+        restore()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
+
+fn enter() -> io::Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
+}
+
+/// Gives the terminal back to the shell. Does nothing if it
+/// has already been restored.
+pub fn restore() -> io::Result<()> {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    // TTYs don't actually have an alternate screen, so the screen is
+    // cleared before leaving it. Otherwise this only modifies the
+    // alternate screen which has no effect.
+    let screen = execute!(
+        io::stdout(),
+        cursor::MoveTo(0, 0),
+        terminal::Clear(terminal::ClearType::All),
+        DisableMouseCapture,
+        LeaveAlternateScreen,
+        cursor::Show
+    );
+    let raw_mode = terminal::disable_raw_mode();
+
+    screen.and(raw_mode)
+}
+
+/// Restores the terminal and stops the process like the default SIGTSTP
+/// handler would. Takes the terminal over again once the process is
+/// continued. The caller is responsible for redrawing everything.
+#[cfg(unix)]
+pub fn suspend() -> io::Result<()> {
+    // Nothing to hand back, e.g. while replaying a recording.
+    if !ACTIVE.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    restore()?;
+    low_level::emulate_default_handler(signal::SIGTSTP)?;
+    ACTIVE.store(true, Ordering::SeqCst);
+    enter()
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+const TERM_SIGNALS: [i32; 3] = [signal::SIGTERM, signal::SIGINT, signal::SIGHUP];
+#[cfg(not(unix))]
+const TERM_SIGNALS: [i32; 2] = [signal::SIGTERM, signal::SIGINT];
+
+/// signal-hook keeps its handler installed once a signal is
+/// unregistered, which would ignore the signal from then on. Set
+/// while no `Signals` is registered, so the default action runs
+/// for the signals that had it before the view started.
+#[cfg(unix)]
+static DEFAULTS: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+#[cfg(unix)]
+fn defaults() -> io::Result<Arc<AtomicBool>> {
+    let mut defaults = DEFAULTS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(defaults) = defaults.as_ref() {
+        return Ok(Arc::clone(defaults));
+    }
+
+    let restore = Arc::new(AtomicBool::new(false));
+    for sig in TERM_SIGNALS.into_iter().chain([signal::SIGTSTP]) {
+        // Signals the program handles itself are left to it.
+        if has_default_action(sig) {
+            flag::register_conditional_default(sig, Arc::clone(&restore))?;
+        }
+    }
+    *defaults = Some(Arc::clone(&restore));
+    Ok(restore)
+}
+
+#[cfg(unix)]
+fn has_default_action(sig: i32) -> bool {
+    // SAFETY: a null action only reads the current one.
+    unsafe {
+        let mut current: libc::sigaction = std::mem::zeroed();
+        libc::sigaction(sig, std::ptr::null(), &mut current) == 0
+            && current.sa_sigaction == libc::SIG_DFL
+    }
+}
+
+/// Signals the event loop checks between frames. Terminating
+/// signals make the loop return so that the terminal is restored
+/// on the way out. A second one kills the process right away
+/// in case the loop is stuck.
+pub struct Signals {
+    terminate: Arc<AtomicBool>,
+    received: Arc<AtomicUsize>,
+    suspend: Arc<AtomicBool>,
+    ids: Vec<SigId>,
+}
+
+impl Signals {
+    pub fn register() -> io::Result<Self> {
+        #[cfg(unix)]
+        defaults()?.store(false, Ordering::SeqCst);
+
+        let mut signals = Self {
+            terminate: Arc::new(AtomicBool::new(false)),
+            received: Arc::new(AtomicUsize::new(0)),
+            suspend: Arc::new(AtomicBool::new(false)),
+            ids: Vec::new(),
+        };
+
+        for sig in TERM_SIGNALS {
+            signals.ids.push(flag::register_conditional_shutdown(
+                sig,
+                1,
+                Arc::clone(&signals.terminate),
+            )?);
+            signals.ids.push(flag::register_usize(
+                sig,
+                Arc::clone(&signals.received),
+                sig as usize,
+            )?);
+            signals
+                .ids
+                .push(flag::register(sig, Arc::clone(&signals.terminate))?);
+        }
+
+        #[cfg(unix)]
+        signals.ids.push(flag::register(
+            signal::SIGTSTP,
+            Arc::clone(&signals.suspend),
+        )?);
+
+        Ok(signals)
+    }
+
+    /// The terminating signal that was received, if any.
+    pub fn terminated(&self) -> Option<i32> {
+        if self.terminate.load(Ordering::SeqCst) {
+            Some(self.received.load(Ordering::SeqCst) as i32)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a SIGTSTP was received since the last call.
+    pub fn take_suspend(&self) -> bool {
+        self.suspend.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        for id in self.ids.drain(..) {
+            low_level::unregister(id);
+        }
+        #[cfg(unix)]
+        if let Ok(defaults) = defaults() {
+            defaults.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        os::unix::process::ExitStatusExt,
+        process::{Command, Stdio},
+        thread,
+        time::Duration,
+    };
+
+    const CHILD_ENV_VAR: &str = "TUI_VIEW_SIGNAL_TEST_CHILD";
+
+    /// Runs in a child process started by `sigint_terminates_after_the_view`.
+    #[test]
+    fn sigint_child() {
+        if env::var_os(CHILD_ENV_VAR).is_none() {
+            return;
+        }
+        // What `create_view` does around the event loop.
+        drop(Signals::register().unwrap());
+
+        low_level::raise(signal::SIGINT).unwrap();
+        thread::sleep(Duration::from_secs(5));
+    }
+
+    #[test]
+    fn sigint_terminates_after_the_view() {
+        let status = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "terminal::tests::sigint_child", "--nocapture"])
+            .env(CHILD_ENV_VAR, "1")
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+
+        assert_eq!(status.signal(), Some(signal::SIGINT));
+    }
+}
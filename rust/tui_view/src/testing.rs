@@ -0,0 +1,427 @@
+//! Helpers for testing apps built on this library without a terminal.
+//!
+//! Sessions recorded with the `TUI_VIEW_RECORD` environment variable set
+//! to a file path can be replayed with [`replay`], which returns the
+//! frames that were drawn along the way as plain text.
+//!
+//! [`render_to_string`] and [`render_to_ansi`] draw a single frame of an
+//! `App` and [`assert_snapshot`] compares it with a golden file:
+//!
+//! ```no_run
+//! # use std::rc::Rc;
+//! # use tui_view::{testing, App, Opts, Page};
+//! # struct MyOpts;
+//! # impl Opts for MyOpts {
+//! #     fn get_pages(&self) -> Vec<Page> { vec![] }
+//! # }
+//! let mut app = App::new(Rc::new(MyOpts));
+//! app.show_popup = true;
+//! app.popup_content = String::from("Installed");
+//! testing::assert_snapshot("tests/snapshots/popup.txt", &testing::render_to_string(&mut app, 80, 24));
+//! ```
+//!
+//! Run the tests with `TUI_VIEW_UPDATE_SNAPSHOTS=1` to write the
+//! golden files instead of comparing with them.
+
+use ratatui::{
+    backend::{Backend, TestBackend},
+    buffer::Cell,
+    layout::Rect,
+    style::{Color, Modifier},
+    Terminal,
+};
+use std::{
+    collections::VecDeque,
+    env,
+    fmt::Write,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::error::Result;
+use crate::events::{EventSource, RecordedEvent, RecordingHeader};
+use crate::view::{run_app, ui};
+use crate::{App, Error, Opts};
+
+pub use ratatui::buffer::Buffer;
+
+/// Set to write golden files in `assert_snapshot` instead of comparing.
+pub const UPDATE_ENV_VAR: &str = "TUI_VIEW_UPDATE_SNAPSHOTS";
+
+/// Feeds recorded events with their original timing, but
+/// without actually waiting for the time to pass.
+struct ReplayEvents {
+    events: VecDeque<RecordedEvent>,
+    /// Milliseconds since the recording started.
+    now: u64,
+    drained: bool,
+}
+
+impl EventSource for ReplayEvents {
+    fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        let timeout = timeout.as_millis() as u64;
+        match self.events.front() {
+            Some(next) if next.ms <= self.now + timeout => {
+                self.now = self.now.max(next.ms);
+                Ok(true)
+            }
+            Some(_) => {
+                self.now += timeout;
+                Ok(false)
+            }
+            // One last timeout so that pending searches run.
+            None => {
+                self.drained = true;
+                Ok(false)
+            }
+        }
+    }
+
+    fn read(&mut self) -> io::Result<crossterm::event::Event> {
+        match self.events.pop_front() {
+            Some(recorded) => Ok(recorded.event),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.drained
+    }
+}
+
+/// A test backend that keeps a copy of every distinct frame.
+struct RecordingBackend {
+    backend: TestBackend,
+    frames: Vec<Buffer>,
+}
+
+impl Backend for RecordingBackend {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        self.backend.draw(content)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.backend.hide_cursor()
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.backend.show_cursor()
+    }
+
+    fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
+        self.backend.get_cursor()
+    }
+
+    fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
+        self.backend.set_cursor(x, y)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.backend.clear()
+    }
+
+    fn size(&self) -> io::Result<Rect> {
+        self.backend.size()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let buffer = self.backend.buffer();
+        if self.frames.last() != Some(buffer) {
+            self.frames.push(buffer.clone());
+        }
+        self.backend.flush()
+    }
+}
+
+fn invalid_data(err: serde_json::Error) -> Error {
+    Error::Recording(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Runs the app with the events recorded in the file at `path` and
+/// returns every distinct frame drawn, rendered with [`buffer_to_string`].
+/// Frames are drawn at the terminal size the recording started with.
+///
+/// Previews computed by `Opts::preview` run on their own thread in real
+/// time, so frames depending on them can differ between runs.
+pub fn replay(opts: Rc<dyn Opts>, path: impl AsRef<Path>) -> Result<Vec<String>> {
+    let file = File::open(path).map_err(Error::Recording)?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header = match lines.next() {
+        Some(line) => line.map_err(Error::Recording)?,
+        None => return Err(Error::Recording(io::ErrorKind::UnexpectedEof.into())),
+    };
+    let header: RecordingHeader = serde_json::from_str(&header).map_err(invalid_data)?;
+
+    let mut events = VecDeque::new();
+    for line in lines {
+        let line = line.map_err(Error::Recording)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push_back(serde_json::from_str(&line).map_err(invalid_data)?);
+    }
+
+    let backend = RecordingBackend {
+        backend: TestBackend::new(header.width, header.height),
+        frames: Vec::new(),
+    };
+    let mut terminal = Terminal::new(backend)?;
+    let mut source = ReplayEvents {
+        events,
+        now: 0,
+        drained: false,
+    };
+
+    run_app(&mut terminal, App::new(opts), None, &mut source)?;
+
+    Ok(terminal
+        .backend()
+        .frames
+        .iter()
+        .map(buffer_to_string)
+        .collect())
+}
+
+/// Renders the text of a buffer, one line per row, without
+/// trailing whitespace. Styles are ignored.
+pub fn buffer_to_string(buffer: &Buffer) -> String {
+    let area = buffer.area();
+    let mut lines = Vec::with_capacity(area.height as usize);
+
+    for y in area.top()..area.bottom() {
+        let mut line = String::new();
+        // Wide characters take two cells and the second one is blank.
+        let mut skip = 0;
+        for x in area.left()..area.right() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let symbol = &buffer.get(x, y).symbol;
+            skip = symbol.width().saturating_sub(1);
+            line.push_str(symbol);
+        }
+        lines.push(line.trim_end().to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Draws one frame of `app` on a terminal of the given size.
+/// Whatever is in `app.search` is applied first, like the
+/// event loop does once the user stops typing.
+pub fn render(app: &mut App, width: u16, height: u16) -> Buffer {
+    let mut terminal =
+        Terminal::new(TestBackend::new(width, height)).expect("test backend can't fail");
+    app.apply_search();
+    app.load_contents();
+    terminal
+        .draw(|f| ui(f, app))
+        .expect("test backend can't fail");
+    terminal.backend().buffer().clone()
+}
+
+/// Draws one frame of `app` as plain text.
+pub fn render_to_string(app: &mut App, width: u16, height: u16) -> String {
+    buffer_to_string(&render(app, width, height))
+}
+
+/// Draws one frame of `app` as text with ANSI escape codes for styles,
+/// so highlighting and marks show up in the snapshot too.
+pub fn render_to_ansi(app: &mut App, width: u16, height: u16) -> String {
+    buffer_to_ansi(&render(app, width, height))
+}
+
+/// Like [`buffer_to_string`] but with the styles as ANSI escape codes.
+/// Every line starts unstyled, so lines can be compared on their own.
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let area = buffer.area();
+    let mut lines = Vec::with_capacity(area.height as usize);
+
+    for y in area.top()..area.bottom() {
+        let mut line = String::new();
+        let mut style = None;
+        let mut skip = 0;
+        for x in area.left()..area.right() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            let cell = buffer.get(x, y);
+            let cell_style = (cell.fg, cell.bg, cell.modifier);
+            if style != Some(cell_style) {
+                line.push_str(&sgr(cell));
+                style = Some(cell_style);
+            }
+            skip = cell.symbol.width().saturating_sub(1);
+            line.push_str(&cell.symbol);
+        }
+        line.push_str("\x1b[0m");
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// The escape code that resets the style and then sets the one of `cell`.
+fn sgr(cell: &Cell) -> String {
+    let mut codes = vec![String::from("0")];
+
+    let modifiers = [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::SLOW_BLINK, "5"),
+        (Modifier::RAPID_BLINK, "6"),
+        (Modifier::REVERSED, "7"),
+        (Modifier::HIDDEN, "8"),
+        (Modifier::CROSSED_OUT, "9"),
+    ];
+    for (modifier, code) in modifiers {
+        if cell.modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+
+    if let Some(code) = color_code(cell.fg, false) {
+        codes.push(code);
+    }
+    if let Some(code) = color_code(cell.bg, true) {
+        codes.push(code);
+    }
+
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let offset = if background { 10 } else { 0 };
+    let code = match color {
+        Color::Reset => return None,
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::Gray => 37,
+        Color::DarkGray => 90,
+        Color::LightRed => 91,
+        Color::LightGreen => 92,
+        Color::LightYellow => 93,
+        Color::LightBlue => 94,
+        Color::LightMagenta => 95,
+        Color::LightCyan => 96,
+        Color::White => 97,
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", 38 + offset, r, g, b)),
+        Color::Indexed(i) => return Some(format!("{};5;{}", 38 + offset, i)),
+    };
+    Some((code + offset).to_string())
+}
+
+/// Compares `actual` with the golden file at `path` and panics with a
+/// line diff if they differ. With `TUI_VIEW_UPDATE_SNAPSHOTS` set, the
+/// golden file is written instead.
+#[track_caller]
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    let path = path.as_ref();
+
+    if env::var_os(UPDATE_ENV_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("could not create the snapshot directory");
+        }
+        fs::write(path, actual).expect("could not write the snapshot");
+        return;
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(err) => panic!(
+            "could not read snapshot {}: {}\nrun with {}=1 to create it",
+            path.display(),
+            err,
+            UPDATE_ENV_VAR
+        ),
+    };
+
+    if expected != actual {
+        panic!(
+            "snapshot {} does not match (- expected, + actual):\n{}\nrun with {}=1 to update it",
+            path.display(),
+            diff(&expected, actual),
+            UPDATE_ENV_VAR
+        );
+    }
+}
+
+/// A line diff of two texts based on their longest common subsequence.
+/// Escape codes are shown escaped so ANSI snapshots stay readable.
+fn diff(expected: &str, actual: &str) -> String {
+    let old = expected.lines().collect::<Vec<&str>>();
+    let new = actual.lines().collect::<Vec<&str>>();
+
+    // lcs[i][j] is the length of the longest common
+    // subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            let _ = writeln!(out, "  {}", old[i].escape_debug());
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", old[i].escape_debug());
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", new[j].escape_debug());
+            j += 1;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let expected = "one\ntwo\nthree";
+        let actual = "one\n2\nthree\nfour";
+        assert_eq!(
+            diff(expected, actual),
+            "  one\n- two\n+ 2\n  three\n+ four\n"
+        );
+    }
+
+    #[test]
+    fn diff_escapes_ansi_codes() {
+        assert_eq!(diff("\x1b[0mx", "x"), "- \\u{1b}[0mx\n+ x\n");
+    }
+
+    #[test]
+    fn diff_of_equal_texts_has_no_changes() {
+        assert_eq!(diff("a\nb", "a\nb"), "  a\n  b\n");
+    }
+}
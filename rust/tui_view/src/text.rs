@@ -0,0 +1,159 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Brings text into a form where strings that read the same compare
+/// equal. Compatibility characters like full width letters or
+/// ligatures are decomposed, diacritics are dropped from Latin letters
+/// and case is folded, so "Ｃａｆé" and "cafe" normalize to the same
+/// string.
+pub fn normalize(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    // Whether the marks that follow belong to a Latin letter.
+    let mut after_latin = false;
+    for c in text.nfkd() {
+        if !is_combining_mark(c) {
+            after_latin = is_latin(c);
+        } else if after_latin {
+            // In other scripts, like the vowel signs of Devanagari or
+            // Thai or the voicing marks of kana, marks make a different
+            // letter rather than accenting it.
+            continue;
+        }
+        stripped.push(c);
+    }
+
+    caseless::default_case_fold_str(&stripped).nfkc().collect()
+}
+
+/// Latin letters as they are after `nfkd`, which
+/// takes most of them apart into ASCII and marks.
+fn is_latin(c: char) -> bool {
+    matches!(c,
+        'A'..='Z'
+        | 'a'..='z'
+        | '\u{00C0}'..='\u{024F}'
+        | '\u{1E00}'..='\u{1EFF}'
+        | '\u{2C60}'..='\u{2C7F}'
+        | '\u{A720}'..='\u{A7FF}'
+        | '\u{AB30}'..='\u{AB6F}'
+    )
+}
+
+/// Removes the last grapheme cluster, so a flag or an emoji
+/// with a skin tone is deleted with a single backspace.
+pub fn pop_grapheme(chars: &mut Vec<char>) {
+    let text = chars.iter().collect::<String>();
+    if let Some((start, _)) = text.grapheme_indices(true).next_back() {
+        chars.truncate(text[..start].chars().count());
+    }
+}
+
+/// The end of `text` that fits into `width` terminal columns and how
+/// many columns it takes. Wide characters like CJK take two columns.
+pub fn fit_tail(text: &str, width: usize) -> (&str, usize) {
+    let mut used = 0;
+    let mut start = text.len();
+
+    for (i, grapheme) in text.grapheme_indices(true).rev() {
+        let grapheme_width = grapheme.width();
+        if used + grapheme_width > width {
+            break;
+        }
+        used += grapheme_width;
+        start = i;
+    }
+
+    (&text[start..], used)
+}
+
+/// Scores `haystack` by how well the characters of `needle` appear in
+/// it in order. Consecutive characters and characters at the start of
+/// words count more. None if some character doesn't appear at all.
+pub fn fuzzy_score(needle: &str, haystack: &str) -> Option<u32> {
+    let needle = normalize(needle);
+    let haystack = normalize(haystack);
+    let mut needle_chars = needle.chars().filter(|c| !c.is_whitespace()).peekable();
+
+    let mut score = 0;
+    let mut previous_matched = false;
+    let mut previous_char = ' ';
+    for c in haystack.chars() {
+        match needle_chars.peek() {
+            Some(&n) if n == c => {
+                score += 1;
+                if previous_matched {
+                    score += 2;
+                }
+                if !previous_char.is_alphanumeric() {
+                    score += 3;
+                }
+                previous_matched = true;
+                needle_chars.next();
+            }
+            Some(_) => previous_matched = false,
+            None => break,
+        }
+        previous_char = c;
+    }
+
+    needle_chars.peek().is_none().then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_folds_width_case_and_accents() {
+        assert_eq!(normalize("Ｃａｆé"), "cafe");
+        assert_eq!(normalize("Ｃａｆé"), normalize("CAFE"));
+        assert_eq!(normalize("ﬁle"), "file");
+        assert_eq!(normalize("Straße"), "strasse");
+    }
+
+    #[test]
+    fn normalize_keeps_kana_voicing() {
+        assert_ne!(normalize("が"), normalize("か"));
+        assert_eq!(normalize("が"), normalize("か\u{3099}"));
+    }
+
+    #[test]
+    fn normalize_keeps_marks_of_other_scripts() {
+        // कि and कु are different syllables, not an accented क.
+        assert_ne!(normalize("कि"), normalize("कु"));
+        assert_ne!(normalize("कि"), normalize("क"));
+        assert_ne!(normalize("กิน"), normalize("กน"));
+        // Latin letters still lose theirs, wherever they are.
+        assert_eq!(normalize("नमस्ते café"), "नमस्ते cafe");
+    }
+
+    fn popped(text: &str) -> String {
+        let mut chars = text.chars().collect::<Vec<char>>();
+        pop_grapheme(&mut chars);
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn pop_grapheme_removes_whole_clusters() {
+        assert_eq!(popped("ok 👍🏽"), "ok ");
+        assert_eq!(popped("to 🇯🇵"), "to ");
+        assert_eq!(popped("🇩🇪🇯🇵"), "🇩🇪");
+        assert_eq!(popped("cafe\u{301}"), "caf");
+        assert_eq!(popped(""), "");
+    }
+
+    #[test]
+    fn fit_tail_counts_wide_characters_twice() {
+        assert_eq!(fit_tail("漢字abc", 4), ("abc", 3));
+        assert_eq!(fit_tail("漢字abc", 5), ("字abc", 5));
+        assert_eq!(fit_tail("漢字abc", 10), ("漢字abc", 7));
+        assert_eq!(fit_tail("漢字", 1), ("", 0));
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_word_starts() {
+        assert!(fuzzy_score("tp", "toggle popup") > fuzzy_score("tp", "output"));
+        assert_eq!(fuzzy_score("xyz", "toggle popup"), None);
+    }
+}
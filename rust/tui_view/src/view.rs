@@ -1,18 +1,29 @@
 use ratatui::{layout::Rect, widgets::Clear};
-use std::{io, rc::Rc, time::Duration};
+use std::{cell::RefCell, rc::Rc, time::Duration};
 
 use crossterm::event::{self, Event, KeyCode, KeyModifiers, MouseEvent, MouseEventKind};
 use ratatui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout},
     style::{Modifier, Style},
-    text::Text,
+    text::{Span, Spans, Text},
     widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap},
     Frame, Terminal,
 };
 
+use crate::command::Builtin;
+use crate::error::Result;
+use crate::events::EventSource;
+use crate::highlight::Highlighter;
+use crate::keybind::{self, Keybind};
+use crate::palette::{self, Palette, PaletteEvent, Target};
+use crate::preview::Previews;
+use crate::terminal::{self, Signals};
+use crate::text;
+use crate::Error;
 use crate::Opts;
-use crate::Page;
+use crate::PageSource;
+use crate::{Link, Page};
 
 #[derive(Clone)]
 pub struct App {
@@ -31,25 +42,252 @@ pub struct App {
     show_dock: bool,
     pub show_popup: bool,
     pub popup_content: String,
+    show_help: bool,
+    help_scroll: u16,
+    palette: Option<Palette>,
     opts: Rc<dyn Opts>,
+    source: Option<Rc<dyn PageSource>>,
+    /// Whether the source has returned its last page.
+    exhausted: bool,
+    /// Whether the last fetch or load from the source failed. It isn't
+    /// asked again while the popup with the error is open, so a failing
+    /// source isn't hammered on every key press, but is once it's closed.
+    source_failed: bool,
+    /// Where the range selection started, if one is in progress.
+    visual_start: Option<usize>,
+    /// Whether the dock shows relevancies and the reader
+    /// how the selected page's relevancy adds up.
+    show_relevancy: bool,
+    /// The title of the page and the index of
+    /// its link that is selected in the reader.
+    link: Option<(String, usize)>,
+    /// Titles of the pages left by following links.
+    back: Vec<String>,
+    /// Titles of the pages left by going back.
+    forward: Vec<String>,
+    /// Shared between clones since it owns the preview thread.
+    previews: Rc<RefCell<Previews>>,
+    highlighter: Rc<RefCell<Highlighter>>,
 }
 
+/// How close the selection can get to the end of
+/// the dock before more pages are fetched.
+const PREFETCH_DISTANCE: usize = 10;
+
 impl App {
     pub fn new(opts: Rc<dyn Opts>) -> Self {
-        App {
+        let source = opts.page_source();
+        let pages = match source {
+            Some(_) => Vec::new(),
+            None => opts.get_pages(),
+        };
+
+        let mut app = App {
             state: TableState::default(),
-            current_pages: opts.get_pages(),
+            current_pages: pages.clone(),
             scroll: 0,
             search: Vec::new(),
-            pages: opts.get_pages(),
+            pages,
             latest_search: Vec::new(),
             show_dock: true,
             show_popup: false,
             popup_content: String::default(),
+            show_help: false,
+            help_scroll: 0,
+            palette: None,
             opts,
+            source: None,
+            exhausted: true,
+            source_failed: false,
+            visual_start: None,
+            show_relevancy: false,
+            link: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            previews: Rc::new(RefCell::new(Previews::new())),
+            highlighter: Rc::new(RefCell::new(Highlighter::new())),
+        };
+
+        if let Some(source) = source {
+            app.set_source(source);
+        }
+
+        app
+    }
+
+    /// Replaces all pages with the first batch from `source`.
+    /// The rest is fetched as the user scrolls down.
+    pub fn set_source(&mut self, source: Rc<dyn PageSource>) {
+        self.clear_previews();
+        self.source = Some(source);
+        self.exhausted = false;
+        self.source_failed = false;
+        self.pages = Vec::new();
+        self.current_pages = Vec::new();
+        self.state.select(None);
+        self.scroll = 0;
+        self.fetch_more();
+    }
+
+    /// Fetches the next batch of pages. Returns false if
+    /// there was nothing to fetch or the source failed.
+    fn fetch_more(&mut self) -> bool {
+        let source = match (&self.source, self.exhausted) {
+            (Some(source), false) if !self.waiting_on_failure() => source.clone(),
+            _ => return false,
+        };
+
+        let count = source.page_size();
+        if count == 0 {
+            // Asking for no pages would never get to the end.
+            self.exhausted = true;
+            return false;
+        }
+        match source.fetch(self.pages.len(), count) {
+            Ok(pages) => {
+                self.source_failed = false;
+                self.exhausted = pages.len() < count;
+                self.pages.extend(pages);
+                self.search_keeping_positions();
+                true
+            }
+            Err(err) => {
+                self.report_source_error(err.to_string());
+                false
+            }
         }
     }
-    fn next(&mut self) {
+
+    fn waiting_on_failure(&self) -> bool {
+        self.source_failed && self.show_popup
+    }
+
+    fn report_source_error(&mut self, message: String) {
+        self.source_failed = true;
+        self.show_popup = true;
+        self.popup_content = message;
+    }
+
+    /// Fetches every remaining page from the source.
+    pub(crate) fn fetch_all(&mut self) -> Result<()> {
+        while !self.exhausted {
+            if !self.fetch_more() && self.source_failed {
+                return Err(Error::Source(self.popup_content.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load_contents(&mut self) {
+        let (source, i) = match (&self.source, self.state.selected()) {
+            (Some(source), Some(i)) if !self.waiting_on_failure() => (source.clone(), i),
+            _ => return,
+        };
+        let page = match self.current_pages.get(i) {
+            Some(page) if !page.contents_loaded => page,
+            _ => return,
+        };
+
+        // Left unloaded on failure so it is tried again.
+        let contents = match source.load_contents(page) {
+            Ok(contents) => contents,
+            Err(err) => return self.report_source_error(err.to_string()),
+        };
+        self.source_failed = false;
+
+        self.update_page(i, |page| page.set_contents(contents.clone()));
+    }
+
+    /// Applies `f` to the page at `i` in current_pages and to the
+    /// page it was copied from in pages, so the change survives
+    /// the next search.
+    fn update_page(&mut self, i: usize, f: impl Fn(&mut Page)) {
+        let id = match self.current_pages.get(i) {
+            Some(page) => page.id,
+            None => return,
+        };
+
+        let originals = self.pages.iter_mut().filter(|p| p.id == id);
+        for page in originals.chain(self.current_pages.get_mut(i)) {
+            f(page);
+        }
+    }
+
+    /// Searches again for the latest search, e.g. to take in newly
+    /// fetched pages. These can be sorted in above the selection, so
+    /// it and the range selection follow their pages to where they are.
+    fn search_keeping_positions(&mut self) {
+        let id_at = |app: &Self, i: Option<usize>| Some(app.current_pages.get(i?)?.id);
+        let selected = id_at(self, self.state.selected());
+        let visual_start = id_at(self, self.visual_start);
+
+        self.search(self.latest_search.iter().collect());
+
+        let position = |app: &Self, id: Option<u64>| {
+            let id = id?;
+            app.current_pages.iter().position(|page| page.id == id)
+        };
+        if selected.is_some() {
+            self.state.select(position(self, selected));
+        }
+        self.visual_start = position(self, visual_start);
+    }
+
+    /// All marked pages, including the ones hidden by the search.
+    pub fn marked_pages(&self) -> Vec<&Page> {
+        self.pages.iter().filter(|page| page.marked).collect()
+    }
+
+    pub fn clear_marks(&mut self) {
+        self.visual_start = None;
+        for page in self.pages.iter_mut().chain(self.current_pages.iter_mut()) {
+            page.marked = false;
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.toggle_mark_at(i);
+            self.next();
+        }
+    }
+
+    pub(crate) fn toggle_mark_at(&mut self, i: usize) {
+        self.update_page(i, |page| page.marked = !page.marked);
+    }
+
+    /// Marks every page that matches the search, or unmarks
+    /// them if they are all marked already.
+    pub(crate) fn toggle_mark_all(&mut self) {
+        let marked = !self.current_pages.iter().all(|page| page.marked);
+        for i in 0..self.current_pages.len() {
+            self.update_page(i, |page| page.marked = marked);
+        }
+    }
+
+    /// Starts a range selection from the selected page. Calling it
+    /// again marks everything between there and the selected page.
+    fn toggle_visual(&mut self) {
+        match (self.visual_start, self.state.selected()) {
+            (Some(start), Some(end)) => {
+                for i in start.min(end)..=start.max(end) {
+                    self.update_page(i, |page| page.marked = true);
+                }
+                self.visual_start = None;
+            }
+            (None, Some(i)) => self.visual_start = Some(i),
+            _ => {}
+        }
+    }
+
+    fn in_visual_range(&self, i: usize) -> bool {
+        match (self.visual_start, self.state.selected()) {
+            (Some(start), Some(end)) => start.min(end) <= i && i <= start.max(end),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn next(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
                 if i + 1 < self.current_pages.len() {
@@ -62,9 +300,13 @@ impl App {
         };
         self.state.select(Some(i));
         self.scroll = 0;
+
+        if i + PREFETCH_DISTANCE >= self.current_pages.len() {
+            self.fetch_more();
+        }
     }
 
-    fn previous(&mut self) {
+    pub(crate) fn previous(&mut self) {
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -79,16 +321,151 @@ impl App {
         self.scroll = 0;
     }
 
+    pub(crate) fn selected_page(&self) -> Option<&Page> {
+        self.state
+            .selected()
+            .and_then(|i| self.current_pages.get(i))
+    }
+
     fn load(&mut self) -> String {
-        match self.state.selected() {
-            Some(i) => match self.current_pages.get(i) {
-                Some(x) => x.contents.clone(),
-                None => String::from(""),
+        if self.show_relevancy {
+            return self.explain_relevancy();
+        }
+
+        match self.selected_page() {
+            Some(x) => match self.previews.borrow().get(&x.title) {
+                Some(preview) => preview.clone(),
+                None => x.contents.clone(),
             },
             None => String::from(""),
         }
     }
 
+    fn update_preview(&mut self) {
+        let page = self.selected_page();
+        self.previews
+            .borrow_mut()
+            .update(page.map(|p| p.title.as_str()), || {
+                page.and_then(|p| self.opts.preview(p))
+            });
+    }
+
+    /// Drops all cached previews, e.g. after the
+    /// data they were computed from has changed.
+    pub fn clear_previews(&mut self) {
+        self.previews.borrow_mut().clear();
+    }
+
+    /// The link selected in the reader, if it
+    /// belongs to the page that is selected now.
+    pub(crate) fn selected_link(&self) -> Option<(usize, &Link)> {
+        let (title, i) = self.link.as_ref()?;
+        let page = self.selected_page().filter(|p| &p.title == title)?;
+        page.links.get(*i).map(|link| (*i, link))
+    }
+
+    pub(crate) fn cycle_link(&mut self, forward: bool) {
+        let page = match self.selected_page() {
+            Some(page) if !page.links.is_empty() => page,
+            _ => return,
+        };
+        let count = page.links.len();
+        let i = match (self.selected_link(), forward) {
+            (Some((i, _)), true) => (i + 1) % count,
+            (Some((i, _)), false) => (i + count - 1) % count,
+            (None, true) => 0,
+            (None, false) => count - 1,
+        };
+
+        let link = &page.links[i];
+        // Brings the link into view with a couple of lines
+        // above it for context, ignoring wrapped lines.
+        let line = page.contents.find(&link.text).map(|start| {
+            page.contents[..start]
+                .matches('\n')
+                .count()
+                .saturating_sub(2)
+        });
+        self.link = Some((page.title.clone(), i));
+        self.scroll = line.unwrap_or(0) as u16;
+    }
+
+    /// Selects the link at `i` of the selected page, if it has one.
+    pub(crate) fn select_link(&mut self, i: usize) -> bool {
+        match self.selected_page() {
+            Some(page) if i < page.links.len() => {
+                self.link = Some((page.title.clone(), i));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Selects the page the selected link leads to.
+    pub(crate) fn follow_link(&mut self) {
+        let (from, target) = match (self.selected_page(), self.selected_link()) {
+            (Some(page), Some((_, link))) => (page.title.clone(), link.target.clone()),
+            _ => return,
+        };
+
+        if self.go_to(&target) {
+            self.back.push(from);
+            self.forward.clear();
+        }
+    }
+
+    pub(crate) fn go_back(&mut self) {
+        self.walk_history(true);
+    }
+
+    pub(crate) fn go_forward(&mut self) {
+        self.walk_history(false);
+    }
+
+    fn walk_history(&mut self, back: bool) {
+        let target = match back {
+            true => self.back.pop(),
+            false => self.forward.pop(),
+        };
+        let target = match target {
+            Some(target) => target,
+            None => return,
+        };
+
+        let current = self.selected_page().map(|p| p.title.clone());
+        if let (true, Some(current)) = (self.go_to(&target), current) {
+            match back {
+                true => self.forward.push(current),
+                false => self.back.push(current),
+            }
+        }
+    }
+
+    /// Selects the page titled `title`, clearing the search if it is
+    /// filtered out and fetching more pages if it isn't loaded yet.
+    /// Shows a popup and returns false if there is no such page.
+    pub(crate) fn go_to(&mut self, title: &str) -> bool {
+        while !self.pages.iter().any(|p| p.title == title) && self.fetch_more() {}
+        if self.waiting_on_failure() {
+            return false;
+        }
+        if !self.pages.iter().any(|p| p.title == title) {
+            self.show_popup = true;
+            self.popup_content = format!("There is no page called {}", title);
+            return false;
+        }
+
+        if !self.current_pages.iter().any(|p| p.title == title) {
+            self.search.clear();
+            self.apply_search();
+        }
+        let i = self.current_pages.iter().position(|p| p.title == title);
+        self.state.select(i);
+        self.scroll = 0;
+        self.link = None;
+        true
+    }
+
     fn scroll_down(&mut self) {
         self.scroll = self.scroll.checked_add(1).unwrap_or(self.scroll);
     }
@@ -98,7 +475,8 @@ impl App {
     }
 
     fn search(&mut self, search_phrase: String) {
-        let search_phrase = search_phrase.trim().to_lowercase();
+        let search_phrase = text::normalize(search_phrase.trim());
+        let config = self.opts.search_config();
 
         if search_phrase.is_empty() {
             self.current_pages = self.pages.clone();
@@ -109,19 +487,24 @@ impl App {
             .pages
             .iter_mut()
             .map(|page| {
-                page.search(&search_phrase, self.opts.get_keywords());
+                page.search(&search_phrase, self.opts.get_keywords(), &config);
                 page.clone()
             })
             .filter(|md_file| md_file.relevancy > 0)
             .collect();
 
-        self.current_pages.sort_by(|a, b| {
-            if let Some(_sort_field) = a.sort_field {
-                b.sort_field.partial_cmp(&a.sort_field).unwrap()
-            } else {
-                b.relevancy.cmp(&a.relevancy)
-            }
-        });
+        self.current_pages.sort_by(|a, b| config.compare(a, b));
+    }
+
+    /// Filters the pages if the search changed since the last time.
+    pub(crate) fn apply_search(&mut self) {
+        if self.search != self.latest_search {
+            self.latest_search = self.search.clone();
+            // Indices into current_pages are meaningless after this.
+            self.visual_start = None;
+            self.search(self.search.iter().collect());
+            self.state.select(Some(0));
+        }
     }
 
     fn toggle_dock(&mut self) {
@@ -131,35 +514,218 @@ impl App {
     fn toggle_popup(&mut self) {
         self.show_popup = !self.show_popup;
     }
+
+    fn toggle_relevancy(&mut self) {
+        self.show_relevancy = !self.show_relevancy;
+        self.scroll = 0;
+    }
+
+    /// What the reader shows instead of the contents while
+    /// the relevancy breakdown is toggled on.
+    fn explain_relevancy(&self) -> String {
+        let page = match self.selected_page() {
+            Some(page) => page,
+            None => return String::new(),
+        };
+        let needle = text::normalize(self.latest_search.iter().collect::<String>().trim());
+        if needle.is_empty() {
+            return String::from("Type to search to see how the pages are ranked.");
+        }
+
+        let config = self.opts.search_config();
+        let terms = page.explain(&needle, self.opts.get_keywords(), &config);
+        let mut lines = vec![
+            format!("Relevancy of {}: {}", page.title, page.relevancy()),
+            String::from("keyword x size x (contents + title) = score"),
+            String::new(),
+        ];
+        for term in terms {
+            lines.push(format!(
+                "\"{}\": {} x {} x ({} + {}) = {}",
+                term.combination,
+                term.keyword_multiplier,
+                term.needle_size_multiplier,
+                term.count_relevancy,
+                term.title_relevancy,
+                term.score
+            ));
+        }
+        if let Some(sort_field) = page.sort_field {
+            lines.push(String::new());
+            lines.push(format!("Sorted by sort_field: {}", sort_field));
+        }
+        lines.join("\n")
+    }
+
+    fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+        self.help_scroll = 0;
+    }
+
+    fn toggle_palette(&mut self) {
+        self.palette = match self.palette {
+            Some(_) => None,
+            None => Some(Palette::default()),
+        };
+    }
+
+    /// Handles keys while the help overlay is open. Everything but
+    /// Ctrl chords goes here until it is closed.
+    fn help_keybinds(&mut self, key: event::KeyEvent) {
+        match key.code {
+            KeyCode::Esc | KeyCode::F(1) | KeyCode::Char('?') | KeyCode::Char('q') => {
+                self.toggle_help()
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.help_scroll = self.help_scroll.saturating_add(1)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.help_scroll = self.help_scroll.saturating_sub(1)
+            }
+            _ => {}
+        }
+    }
+}
+
+enum Flow {
+    Continue,
+    Exit,
+}
+
+fn run_builtin<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    builtin: Builtin,
+    argument: Option<String>,
+) -> Result<Flow> {
+    match builtin {
+        Builtin::Exit => return Ok(Flow::Exit),
+        Builtin::ScrollDown => app.scroll_down(),
+        Builtin::ScrollUp => app.scroll_up(),
+        Builtin::Next => app.next(),
+        Builtin::Previous => app.previous(),
+        Builtin::ToggleDock => app.toggle_dock(),
+        Builtin::TogglePopup => app.toggle_popup(),
+        Builtin::Suspend => suspend(terminal)?,
+        Builtin::ToggleMark => app.toggle_mark(),
+        Builtin::ToggleVisual => app.toggle_visual(),
+        Builtin::ToggleMarkAll => app.toggle_mark_all(),
+        Builtin::Help => app.toggle_help(),
+        Builtin::Palette => app.toggle_palette(),
+        Builtin::Search => app.search = argument.unwrap_or_default().chars().collect(),
+        Builtin::ToggleRelevancy => app.toggle_relevancy(),
+        Builtin::NextLink => app.cycle_link(true),
+        Builtin::PreviousLink => app.cycle_link(false),
+        Builtin::FollowLink => app.follow_link(),
+        Builtin::Back => app.go_back(),
+        Builtin::Forward => app.go_forward(),
+    }
+    Ok(Flow::Continue)
+}
+
+/// Handles all keys while the command palette is open.
+fn palette_keybinds<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    key: event::KeyEvent,
+) -> Result<Flow> {
+    let entries = palette::entries(app.opts.commands());
+    let palette_event = match app.palette.as_mut() {
+        Some(palette) => palette.handle_key(key, entries),
+        None => return Ok(Flow::Continue),
+    };
+
+    match palette_event {
+        PaletteEvent::Nothing => Ok(Flow::Continue),
+        PaletteEvent::Close => {
+            app.palette = None;
+            Ok(Flow::Continue)
+        }
+        PaletteEvent::Run(entry, argument) => {
+            app.palette = None;
+            match entry.target {
+                Target::Builtin(builtin) => run_builtin(terminal, app, builtin, argument),
+                Target::Opts => {
+                    let opts = app.opts.clone();
+                    *app = opts.run_command(&entry.command.name, argument, app.clone());
+                    Ok(Flow::Continue)
+                }
+            }
+        }
+    }
+}
+
+fn suspend<B: Backend>(terminal: &mut Terminal<B>) -> Result<()> {
+    terminal::suspend().map_err(Error::Terminal)?;
+    // Whatever was on the screen is gone after resuming.
+    terminal.clear()?;
+    Ok(())
 }
 
-pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+pub fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: App,
+    signals: Option<&Signals>,
+    events: &mut dyn EventSource,
+) -> Result<()> {
     loop {
+        if let Some(signals) = signals {
+            if let Some(signal) = signals.terminated() {
+                return Err(Error::Interrupted(signal));
+            }
+            if signals.take_suspend() {
+                suspend(terminal)?;
+            }
+        }
+
+        app.load_contents();
+        app.update_preview();
         terminal.draw(|f| ui(f, &mut app))?;
 
-        if event::poll(Duration::from_millis(200))? {
-            match event::read()? {
+        if events.finished() {
+            return Ok(());
+        }
+
+        // Checks back sooner while a preview is on its way.
+        let timeout = if app.previews.borrow().is_busy() {
+            50
+        } else {
+            200
+        };
+
+        if events.poll(Duration::from_millis(timeout))? {
+            match events.read()? {
+                Event::Key(key) if app.palette.is_some() => {
+                    if let Flow::Exit = palette_keybinds(terminal, &mut app, key)? {
+                        return Ok(());
+                    }
+                }
+                Event::Key(key) if app.show_help && key.modifiers != KeyModifiers::CONTROL => {
+                    app.help_keybinds(key)
+                }
                 Event::Key(key) => {
-                    match key.modifiers {
-                        KeyModifiers::CONTROL => match key.code {
-                            KeyCode::Char('e') => return Ok(()),
-                            KeyCode::Char('d') => app.scroll_down(),
-                            KeyCode::Char('u') => app.scroll_up(),
-                            KeyCode::Char('j') => app.next(),
-                            KeyCode::Char('k') => app.previous(),
-                            KeyCode::Char('b') => app.toggle_dock(),
-                            KeyCode::Char('p') => app.toggle_popup(),
-                            _ => {}
-                        },
-                        KeyModifiers::NONE => {
+                    match Builtin::from_key(&key, app.search.is_empty()) {
+                        Some(builtin) => {
+                            if let Flow::Exit = run_builtin(terminal, &mut app, builtin, None)? {
+                                return Ok(());
+                            }
+                        }
+                        None if matches!(
+                            key.modifiers,
+                            KeyModifiers::NONE | KeyModifiers::SHIFT
+                        ) =>
+                        {
+                            if key.code == KeyCode::Esc {
+                                app.visual_start = None;
+                            }
                             if key.code == KeyCode::Backspace {
-                                app.search.pop();
+                                text::pop_grapheme(&mut app.search);
                             }
                             if let KeyCode::Char(x) = key.code {
                                 app.search.push(x);
                             }
                         }
-                        _ => {}
+                        None => {}
                     }
                     app = app.opts.keybinds(key, app.clone());
                 }
@@ -171,14 +737,95 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Resu
                 }) => app.show_popup = false,
                 _ => {}
             }
-        } else if app.search != app.latest_search {
-            app.latest_search = app.search.clone();
-            app.search(app.search.iter().collect());
-            app.state.select(Some(0));
+        } else {
+            app.apply_search();
         }
     }
 }
 
+/// Shows the first appearance of the selected link in reverse video.
+fn highlight_link(mut text: Text<'static>, link: Option<String>) -> Text<'static> {
+    let link = match link {
+        Some(link) if !link.is_empty() => link,
+        _ => return text,
+    };
+
+    for line in text.lines.iter_mut() {
+        let plain = line
+            .0
+            .iter()
+            .map(|s| s.content.as_ref())
+            .collect::<String>();
+        let (start, end) = match plain.find(&link) {
+            Some(start) => (start, start + link.len()),
+            None => continue,
+        };
+
+        // Spans are split where the link starts and ends so
+        // the parts inside keep their style and get reversed.
+        let mut spans = Vec::new();
+        let mut offset = 0;
+        for span in line.0.drain(..) {
+            let content = span.content.as_ref();
+            let span_end = offset + content.len();
+            let mut cuts = vec![0];
+            cuts.extend(
+                [start, end]
+                    .into_iter()
+                    .filter(|cut| *cut > offset && *cut < span_end)
+                    .map(|cut| cut - offset),
+            );
+            cuts.push(content.len());
+
+            for piece in cuts.windows(2) {
+                let style = if offset + piece[0] >= start && offset + piece[1] <= end {
+                    span.style.add_modifier(Modifier::REVERSED)
+                } else {
+                    span.style
+                };
+                spans.push(Span::styled(content[piece[0]..piece[1]].to_string(), style));
+            }
+            offset = span_end;
+        }
+        line.0 = spans;
+        break;
+    }
+    text
+}
+
+fn help_lines(opts_keybinds: Vec<Keybind>) -> Vec<Spans<'static>> {
+    let sections = [("Built-in", keybind::builtin()), ("App", opts_keybinds)];
+    let width = sections
+        .iter()
+        .flat_map(|(_, keybinds)| keybinds.iter().map(|k| k.keys.chars().count()))
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![];
+    for (title, keybinds) in sections {
+        if keybinds.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(Spans::default());
+        }
+        lines.push(Spans::from(Span::styled(
+            title,
+            Style::default().add_modifier(Modifier::UNDERLINED),
+        )));
+        for k in keybinds {
+            lines.push(Spans::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", k.keys, width = width),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(k.description),
+            ]));
+        }
+    }
+    lines
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -205,7 +852,7 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+pub fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let dock_size = if app.show_dock { 30 } else { 0 };
     let main_layout = Layout::default()
         .direction(Direction::Horizontal)
@@ -222,29 +869,94 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
 
     let mut directory_rows = vec![];
 
-    app.current_pages.iter().for_each(|chapter| {
-        let title = chapter.title.clone();
-        directory_rows.push(Row::new(vec![title]))
-    });
+    let marked_count = app.marked_pages().len();
+    let show_marks = marked_count > 0 || app.visual_start.is_some();
+
+    for (i, chapter) in app.current_pages.iter().enumerate() {
+        let marked = chapter.marked || app.in_visual_range(i);
+        let title = match (show_marks, marked) {
+            (false, _) => chapter.title.clone(),
+            (true, true) => format!("* {}", chapter.title),
+            (true, false) => format!("  {}", chapter.title),
+        };
+        let mut cells = vec![title];
+        if app.show_relevancy {
+            cells.push(chapter.relevancy().to_string());
+        }
+
+        let row = if show_marks && marked {
+            Row::new(cells).style(Style::default().add_modifier(Modifier::BOLD))
+        } else {
+            Row::new(cells)
+        };
+        directory_rows.push(row)
+    }
+
+    let widths: &[Constraint] = match app.show_relevancy {
+        true => &[Constraint::Percentage(75), Constraint::Percentage(25)],
+        false => &[Constraint::Percentage(100)],
+    };
+    let directory_title = match (app.visual_start, marked_count) {
+        (Some(_), _) => String::from("Directory (visual)"),
+        (None, 0) => String::from("Directory"),
+        (None, count) => format!("Directory ({} marked)", count),
+    };
 
     let directory_table = Table::new(directory_rows)
-        .block(Block::default().title("Directory").borders(Borders::ALL))
-        .widths(&[Constraint::Percentage(100)])
+        .block(
+            Block::default()
+                .title(directory_title)
+                .borders(Borders::ALL),
+        )
+        .widths(widths)
         .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
 
-    let page = Paragraph::new(app.load())
-        .block(Block::default().title("Reader").borders(Borders::ALL))
+    let link = match app.show_relevancy {
+        true => None,
+        false => app.selected_link().map(|(i, link)| (i, link.clone())),
+    };
+    let reader_title = match (app.selected_page(), &link) {
+        _ if app.show_relevancy => String::from("Reader (relevancy)"),
+        (Some(page), _) if app.previews.borrow().is_loading(&page.title) => {
+            String::from("Reader (loading preview)")
+        }
+        (Some(page), Some((i, link))) => format!(
+            "Reader (link {}/{}: {})",
+            i + 1,
+            page.links.len(),
+            link.target
+        ),
+        _ => String::from("Reader"),
+    };
+
+    let contents = app.load();
+    let language = app.selected_page().and_then(|p| p.language.clone());
+    let text = match app.show_relevancy {
+        true => Text::from(contents),
+        false => app
+            .highlighter
+            .borrow_mut()
+            .highlight(&contents, language.as_deref()),
+    };
+    let page = Paragraph::new(highlight_link(text, link.map(|(_, link)| link.text)))
+        .block(Block::default().title(reader_title).borders(Borders::ALL))
         .scroll((app.scroll, 0))
         .wrap(Wrap { trim: false });
 
+    // Only the end of a long search is shown so that
+    // what is being typed is always visible.
     let input_string: String = app.search.iter().collect();
-    let lines = Text::from(input_string.as_str());
-    let search = Paragraph::new(lines)
-        .block(Block::default().title("Search").borders(Borders::ALL))
-        .wrap(Wrap { trim: true });
+    let search_area = layout[0];
+    let input_width = search_area.width.saturating_sub(3) as usize;
+    let (visible_input, input_columns) = text::fit_tail(&input_string, input_width);
+    let search = Paragraph::new(Text::from(visible_input))
+        .block(Block::default().title("Search").borders(Borders::ALL));
 
     f.render_widget(page, main_layout[1]);
-    f.render_widget(search, layout[0]);
+    f.render_widget(search, search_area);
+    if app.show_dock && !app.show_popup && !app.show_help && app.palette.is_none() {
+        f.set_cursor(search_area.x + 1 + input_columns as u16, search_area.y + 1);
+    }
     f.render_stateful_widget(directory_table, layout[1], &mut app.state);
     if app.show_popup {
         let size = f.size();
@@ -255,4 +967,257 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         f.render_widget(Clear, area);
         f.render_widget(popup, area);
     }
+    if app.show_help {
+        let help = Paragraph::new(help_lines(app.opts.describe_keybinds()))
+            .block(
+                Block::default()
+                    .title("Help (Esc to close)")
+                    .borders(Borders::ALL),
+            )
+            .scroll((app.help_scroll, 0));
+        let area = centered_rect(60, 60, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(help, area);
+    }
+    if let Some(palette) = &app.palette {
+        let entries = palette::entries(app.opts.commands());
+        render_palette(f, palette, entries);
+    }
+}
+
+fn render_palette<B: Backend>(f: &mut Frame<B>, palette: &Palette, entries: Vec<palette::Entry>) {
+    let area = centered_rect(60, 50, f.size());
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let input: String = palette.input.iter().collect();
+    let (visible_input, input_columns) =
+        text::fit_tail(&input, layout[0].width.saturating_sub(3) as usize);
+    let prompt = match &palette.awaiting_argument {
+        Some(entry) => format!(
+            "{}: {}",
+            entry.command.name,
+            entry.command.argument.clone().unwrap_or_default()
+        ),
+        None => String::from("Command"),
+    };
+    let input_box = Paragraph::new(Text::from(visible_input))
+        .block(Block::default().title(prompt).borders(Borders::ALL));
+
+    let rows = match palette.awaiting_argument {
+        Some(_) => vec![],
+        None => palette
+            .filter(entries)
+            .into_iter()
+            .map(|entry| {
+                Row::new(vec![
+                    entry.command.name,
+                    entry.command.description,
+                    entry.command.keys.unwrap_or_default(),
+                ])
+            })
+            .collect(),
+    };
+    let list = Table::new(rows)
+        .block(Block::default().borders(Borders::ALL))
+        .widths(&[
+            Constraint::Percentage(30),
+            Constraint::Percentage(50),
+            Constraint::Percentage(20),
+        ])
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut list_state = TableState::default();
+    list_state.select(Some(palette.selected));
+
+    f.render_widget(Clear, area);
+    f.render_widget(input_box, layout[0]);
+    f.render_stateful_widget(list, layout[1], &mut list_state);
+    f.set_cursor(layout[0].x + 1 + input_columns as u16, layout[0].y + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SourceResult;
+
+    /// Pages p000 to p029, fetched 12 at a time. Searches
+    /// sort the later ones first.
+    struct NumberedPages;
+
+    impl PageSource for NumberedPages {
+        fn fetch(&self, offset: usize, count: usize) -> SourceResult<Vec<Page>> {
+            Ok((offset..(offset + count).min(30))
+                .map(|i| Page::new(String::new(), format!("p{:03}", i), Some(i as f64)))
+                .collect())
+        }
+
+        fn page_size(&self) -> usize {
+            12
+        }
+    }
+
+    struct SourceOpts;
+
+    impl Opts for SourceOpts {
+        fn get_pages(&self) -> Vec<Page> {
+            Vec::new()
+        }
+
+        fn page_source(&self) -> Option<Rc<dyn PageSource>> {
+            Some(Rc::new(NumberedPages))
+        }
+    }
+
+    /// Pages that would never run out, if they were asked for.
+    struct NoPageSize;
+
+    impl PageSource for NoPageSize {
+        fn fetch(&self, _offset: usize, _count: usize) -> SourceResult<Vec<Page>> {
+            Ok(Vec::new())
+        }
+
+        fn page_size(&self) -> usize {
+            0
+        }
+    }
+
+    struct NoPageSizeOpts;
+
+    impl Opts for NoPageSizeOpts {
+        fn get_pages(&self) -> Vec<Page> {
+            Vec::new()
+        }
+
+        fn page_source(&self) -> Option<Rc<dyn PageSource>> {
+            Some(Rc::new(NoPageSize))
+        }
+    }
+
+    #[test]
+    fn a_page_size_of_zero_exhausts_the_source() {
+        let mut app = App::new(Rc::new(NoPageSizeOpts));
+        assert!(app.exhausted);
+        assert!(app.fetch_all().is_ok());
+        assert!(app.pages.is_empty());
+    }
+
+    /// Fails the first time it is asked, then has one page.
+    struct FlakySource(std::cell::Cell<bool>);
+
+    impl PageSource for FlakySource {
+        fn fetch(&self, _offset: usize, _count: usize) -> SourceResult<Vec<Page>> {
+            if !self.0.replace(true) {
+                return Err("offline".into());
+            }
+            Ok(vec![Page::new(String::new(), String::from("back"), None)])
+        }
+    }
+
+    #[test]
+    fn a_failed_fetch_is_retried_once_the_popup_is_closed() {
+        let mut app = App::new(Rc::new(StaticOpts(Vec::new())));
+        app.set_source(Rc::new(FlakySource(std::cell::Cell::new(false))));
+        assert!(app.show_popup);
+        assert_eq!(app.popup_content, "offline");
+        assert!(matches!(app.fetch_all(), Err(Error::Source(_))));
+
+        app.show_popup = false;
+        assert!(app.fetch_all().is_ok());
+        assert_eq!(app.pages[0].title, "back");
+    }
+
+    struct StaticOpts(Vec<Page>);
+
+    impl Opts for StaticOpts {
+        fn get_pages(&self) -> Vec<Page> {
+            self.0.clone()
+        }
+    }
+
+    fn marked_titles(app: &App) -> Vec<&str> {
+        app.marked_pages()
+            .iter()
+            .map(|page| page.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn range_selection_survives_fetching_more_pages() {
+        let mut app = App::new(Rc::new(SourceOpts));
+        app.next();
+        app.toggle_visual();
+        for _ in 0..5 {
+            app.next();
+        }
+        // Getting close to the end of the first batch fetched more.
+        assert_eq!(app.pages.len(), 24);
+        app.toggle_visual();
+
+        assert_eq!(
+            marked_titles(&app),
+            ["p000", "p001", "p002", "p003", "p004", "p005"]
+        );
+    }
+
+    #[test]
+    fn selections_follow_their_pages_when_fetched_pages_sort_above() {
+        let mut app = App::new(Rc::new(SourceOpts));
+        app.search = "p0".chars().collect();
+        app.apply_search();
+        assert_eq!(app.selected_page().unwrap().title, "p011");
+
+        app.toggle_visual();
+        app.next();
+        app.next();
+        // p012 to p023 were fetched and sorted in above p011.
+        assert_eq!(app.pages.len(), 24);
+        assert_eq!(app.current_pages[0].title, "p023");
+        assert_eq!(app.selected_page().unwrap().title, "p009");
+        app.toggle_visual();
+
+        assert_eq!(marked_titles(&app), ["p009", "p010", "p011"]);
+    }
+
+    #[test]
+    fn marking_a_page_leaves_pages_with_the_same_title() {
+        let pages = vec![
+            Page::new(String::from("one"), String::from("twin"), None),
+            Page::new(String::from("two"), String::from("twin"), None),
+        ];
+        let mut app = App::new(Rc::new(StaticOpts(pages)));
+        app.toggle_mark_at(1);
+
+        let marked = app.marked_pages();
+        assert_eq!(marked.len(), 1);
+        assert_eq!(marked[0].contents, "two");
+    }
+
+    #[test]
+    fn changing_the_search_ends_the_range_selection() {
+        let mut app = App::new(Rc::new(SourceOpts));
+        app.next();
+        app.toggle_visual();
+        app.search = "p01".chars().collect();
+        app.apply_search();
+        app.toggle_visual();
+
+        assert!(marked_titles(&app).is_empty());
+    }
+
+    #[test]
+    fn centered_rect_is_in_the_middle() {
+        let area = Rect::new(0, 0, 100, 40);
+        assert_eq!(centered_rect(50, 50, area), Rect::new(25, 10, 50, 20));
+        assert_eq!(centered_rect(100, 100, area), area);
+    }
+
+    #[test]
+    fn centered_rect_stays_in_an_offset_area() {
+        let area = Rect::new(10, 5, 40, 20);
+        let rect = centered_rect(50, 50, area);
+        assert_eq!(rect, Rect::new(20, 10, 20, 10));
+        assert_eq!(area.intersection(rect), rect);
+    }
 }
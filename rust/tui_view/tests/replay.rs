@@ -0,0 +1,63 @@
+use std::rc::Rc;
+use tui_view::{testing, Opts, Page};
+
+struct Recipes;
+
+impl Opts for Recipes {
+    fn get_pages(&self) -> Vec<Page> {
+        vec![
+            Page::new(
+                String::from("Bake the apples."),
+                String::from("Apple pie"),
+                None,
+            ),
+            Page::new(
+                String::from("Mash the bananas."),
+                String::from("Banana bread"),
+                None,
+            ),
+            Page::new(
+                String::from("Split the bananas."),
+                String::from("Banana split"),
+                None,
+            ),
+            Page::new(
+                String::from("Pit the cherries."),
+                String::from("Cherry jam"),
+                None,
+            ),
+        ]
+    }
+}
+
+/// The fixture types "ban" with 50ms between the keys, pauses
+/// and then moves to the next page with Ctrl-j.
+#[test]
+fn replays_a_recorded_search() {
+    let frames = testing::replay(Rc::new(Recipes), "tests/fixtures/search.jsonl").unwrap();
+    assert_eq!(frames.len(), 6);
+
+    // The search only runs once typing pauses.
+    assert!(frames[3].contains("│ban "));
+    assert!(frames[3].contains("Apple pie"));
+
+    assert!(!frames[4].contains("Apple pie"));
+    assert!(frames[4].contains("Mash the bananas."));
+
+    let last = frames.last().unwrap();
+    assert!(last.contains("Split the bananas."));
+    assert!(last.contains("Banana bread"));
+}
+
+#[test]
+fn replay_rejects_a_missing_header() {
+    let dir = std::env::temp_dir().join("tui_view_replay_test");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("empty.jsonl");
+    std::fs::write(&path, "").unwrap();
+
+    assert!(matches!(
+        testing::replay(Rc::new(Recipes), &path),
+        Err(tui_view::Error::Recording(_))
+    ));
+}
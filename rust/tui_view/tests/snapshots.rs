@@ -0,0 +1,93 @@
+use std::{panic, rc::Rc};
+use tui_view::{testing, App, Opts, Page};
+
+struct Tools;
+
+impl Opts for Tools {
+    fn get_pages(&self) -> Vec<Page> {
+        vec![
+            Page::new(
+                String::from("cargo builds and tests Rust packages."),
+                String::from("cargo"),
+                None,
+            ),
+            Page::new(
+                String::from("rustup installs Rust toolchains."),
+                String::from("rustup"),
+                None,
+            ),
+            Page::new(
+                String::from("```rust\nfn main() {}\n```"),
+                String::from("rustc"),
+                None,
+            ),
+        ]
+    }
+}
+
+fn app() -> App {
+    App::new(Rc::new(Tools))
+}
+
+#[test]
+fn ui() {
+    let mut app = app();
+    testing::assert_snapshot(
+        "tests/snapshots/ui.txt",
+        &testing::render_to_string(&mut app, 60, 12),
+    );
+}
+
+#[test]
+fn ui_with_search() {
+    let mut app = app();
+    app.search = "rust".chars().collect();
+    testing::assert_snapshot(
+        "tests/snapshots/ui_with_search.txt",
+        &testing::render_to_string(&mut app, 60, 12),
+    );
+}
+
+#[test]
+fn ui_styles() {
+    let mut app = app();
+    app.search = "cargo".chars().collect();
+    testing::assert_snapshot(
+        "tests/snapshots/ui.ansi",
+        &testing::render_to_ansi(&mut app, 60, 12),
+    );
+}
+
+#[test]
+fn popup() {
+    let mut app = app();
+    app.show_popup = true;
+    app.popup_content = String::from("Installed cargo");
+    testing::assert_snapshot(
+        "tests/snapshots/popup.txt",
+        &testing::render_to_string(&mut app, 60, 20),
+    );
+}
+
+#[test]
+fn mismatch_panics_with_a_diff() {
+    if std::env::var_os(testing::UPDATE_ENV_VAR).is_some() {
+        return;
+    }
+
+    let mut app = app();
+    app.show_popup = true;
+    app.popup_content = String::from("Removed cargo");
+    let actual = testing::render_to_string(&mut app, 60, 20);
+
+    let err =
+        panic::catch_unwind(|| testing::assert_snapshot("tests/snapshots/popup.txt", &actual))
+            .unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+
+    assert!(message.contains("snapshot tests/snapshots/popup.txt does not match"));
+    assert!(message.contains("\n- "));
+    assert!(message.contains("Installed cargo"));
+    assert!(message.contains("\n+ "));
+    assert!(message.contains("Removed cargo"));
+}